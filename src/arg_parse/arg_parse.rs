@@ -1,6 +1,18 @@
+use man_file::man_volume::ManVolume;
 use man_parse::man_section::ManSection;
 use std::env;
 
+/// How explanations are written out.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum OutputFormat {
+    /// Styled text for a terminal
+    #[default]
+    Text,
+
+    /// Structured data, for other programs to consume
+    Json,
+}
+
 #[derive(Default)]
 pub struct ExplainArgs {
     pub command_name: String,
@@ -10,12 +22,27 @@ pub struct ExplainArgs {
     pub debug_visualize_margin: bool,
     pub help: bool,
     pub section: Option<ManSection>,
+
+    /// The volume of the manual to look for the page in, i.e. 3 for printf(3)
+    pub volume: Option<ManVolume>,
+
+    /// The command and its args as they were given, if a volume was taken from them.
+    /// A name like 'tclsh8.6' or '7z' only looks like it has a volume,
+    /// so without a page in that volume, this is looked up instead.
+    pub unsplit: Option<(String, Vec<String>)>,
+    pub format: OutputFormat,
+
+    /// Show italic text in true italics, rather than underlined
+    pub italics: bool,
 }
 
 // Optional.  Which section should we parse through?
 const SHORT_SECTION_ARG: &str = "-s=";
 const LONG_SECTION_ARG: &str = "--section=";
 
+// Optional.  How should the output be written?  Either "text" (default) or "json".
+const FORMAT_ARG: &str = "--format";
+
 pub fn argparse() -> ExplainArgs {
     let args: Vec<String> = env::args().collect();
 
@@ -24,6 +51,10 @@ pub fn argparse() -> ExplainArgs {
         ::std::process::exit(1);
     }
 
+    parse_args(args)
+}
+
+fn parse_args(args: Vec<String>) -> ExplainArgs {
     let mut result = ExplainArgs::default();
 
     let mut args_iter = args.into_iter();
@@ -44,16 +75,35 @@ pub fn argparse() -> ExplainArgs {
             "-h" | "--help" => result.help = true,
             "-d" | "--debug" => result.debug = true,
             "-dM" | "--debug_visualize_margin" => result.debug_visualize_margin = true,
+            "--italics" => result.italics = true,
             s if s.starts_with(SHORT_SECTION_ARG) | s.starts_with(LONG_SECTION_ARG) => {
                 result.section = parse_section_arg(s)
             }
+            FORMAT_ARG => result.format = parse_format_arg(&args_iter.next().unwrap_or_default()),
+            s if s.starts_with(FORMAT_ARG) && s[FORMAT_ARG.len()..].starts_with('=') => {
+                result.format = parse_format_arg(&s[FORMAT_ARG.len() + 1..])
+            }
             _ => {}
         };
     }
 
     // rest of the iteration is for the arguments of the given command
-    while let Some(arg) = args_iter.next() {
-        result.command_args.push(arg.to_owned());
+    result.command_args.extend(args_iter);
+
+    // the volume may come before the command, i.e. 'explain 3 printf',
+    // or be part of its name, i.e. 'explain printf.3' or 'explain printf(3)'
+    let command_name = result.command_name.clone();
+    if ManVolume::parse(&command_name).is_some() && !result.command_args.is_empty() {
+        result.unsplit = Some((command_name.clone(), result.command_args.clone()));
+        result.volume = ManVolume::parse(&command_name);
+        result.command_name = result.command_args.remove(0);
+    } else {
+        let (name, volume) = ManVolume::split_page_name(&command_name);
+        if volume.is_some() {
+            result.unsplit = Some((command_name.clone(), result.command_args.clone()));
+            result.command_name = name.to_owned();
+            result.volume = volume;
+        }
     }
 
     result
@@ -69,6 +119,63 @@ fn parse_section_arg(section_arg: &str) -> Option<ManSection> {
     }
 }
 
+fn parse_format_arg(format: &str) -> OutputFormat {
+    match format.to_lowercase().as_str() {
+        "json" => OutputFormat::Json,
+        "text" => OutputFormat::Text,
+        _ => {
+            println!("Unknown format: {} (expected 'text' or 'json')", format);
+            ::std::process::exit(1);
+        }
+    }
+}
+
 fn print_usage() {
     println!("Usage: TODO");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> ExplainArgs {
+        let args = ["explain"].iter().chain(args).map(|arg| arg.to_string());
+        parse_args(args.collect())
+    }
+
+    #[test]
+    fn test_volumes() {
+        let args = parse(&["3", "printf"]);
+        assert_eq!(args.command_name, "printf");
+        assert_eq!(args.volume, ManVolume::parse("3"));
+
+        let args = parse(&["printf(3)"]);
+        assert_eq!(args.command_name, "printf");
+        assert_eq!(args.volume, ManVolume::parse("3"));
+    }
+
+    #[test]
+    fn test_names_that_look_like_volumes_are_kept() {
+        let args = parse(&["tclsh8.6"]);
+        assert_eq!(args.command_name, "tclsh8");
+        assert_eq!(args.unsplit, Some(("tclsh8.6".to_owned(), vec![])));
+
+        let args = parse(&["perl5.36.0", "-e", "1"]);
+        assert_eq!(
+            args.unsplit,
+            Some((
+                "perl5.36.0".to_owned(),
+                vec!["-e".to_owned(), "1".to_owned()]
+            ))
+        );
+
+        let args = parse(&["7z", "x", "a.7z"]);
+        assert_eq!(args.command_name, "x");
+        assert_eq!(
+            args.unsplit,
+            Some(("7z".to_owned(), vec!["x".to_owned(), "a.7z".to_owned()]))
+        );
+
+        assert!(parse(&["ls", "-l"]).unsplit.is_none());
+    }
+}
@@ -0,0 +1,267 @@
+use man_parse::option_entry::OptionEntry;
+use man_parse::term_writer::font_style::{FontStyle, StyleSpan};
+use man_parse::troff_parser::{render_tokens_plain, ExplainedArg, Heading, TroffParser};
+use man_parse::troff_token_generator::TroffToken;
+use serde_json::Value;
+use shell_parse::command_line::SimpleCommand;
+use simple_parser::token::Token;
+
+/// A parsed page as JSON.
+/// If args were explained, that is each arg with the entry explaining it.
+/// Otherwise, it is the structure of the page: its sections, and the options it documents.
+pub fn page_json<'a, I>(page: &str, parser: &TroffParser<'a, I>) -> Value
+where
+    I: Iterator<Item = &'a Token<TroffToken>>,
+{
+    if !parser.explained_args().is_empty() {
+        let args: Vec<Value> = parser.explained_args().iter().map(arg_json).collect();
+
+        return json!({
+            "page": page,
+            "args": args,
+        });
+    }
+
+    let options: Vec<Value> = parser.option_entries().iter().map(entry_json).collect();
+
+    json!({
+        "page": page,
+        "sections": sections_json(parser.result_text(), parser.style_spans(), parser.headings()),
+        "options": options,
+    })
+}
+
+/// A command of a shell command line as JSON,
+/// along with the explanation of its page, if it has one,
+/// and the commands it runs, if it's a utility like xargs or sudo.
+pub fn command_json(
+    command_line: &str,
+    command: &SimpleCommand,
+    page: Option<Value>,
+    nested_commands: Vec<Value>,
+) -> Value {
+    let expansions: Vec<Value> = command
+        .expansions
+        .iter()
+        .map(|expansion| {
+            json!({
+                "text": &command_line[expansion.span.clone()],
+                "start": expansion.span.start,
+                "end": expansion.span.end,
+                "explanation": expansion.explain(),
+            })
+        })
+        .collect();
+
+    let redirections: Vec<Value> = command
+        .redirections
+        .iter()
+        .map(|redirection| {
+            json!({
+                "text": redirection.to_string(),
+                "explanation": redirection.explain(),
+            })
+        })
+        .collect();
+
+    let separator = command.separator.map(|separator| {
+        json!({
+            "operator": separator.operator(),
+            "explanation": separator.explain(),
+        })
+    });
+
+    json!({
+        "text": &command_line[command.span.clone()],
+        "start": command.span.start,
+        "end": command.span.end,
+        "assignments": command.assignments,
+        "words": command.words,
+        "page": page,
+        "nested_commands": nested_commands,
+        "expansions": expansions,
+        "redirections": redirections,
+        "separator": separator,
+    })
+}
+
+fn arg_json(arg: &ExplainedArg) -> Value {
+    json!({
+        "arg": arg.arg,
+        "value": arg.value,
+        "entry": arg.entry.as_ref().map(entry_json),
+    })
+}
+
+fn entry_json(entry: &OptionEntry) -> Value {
+    let placeholder = entry.placeholder.as_ref().map(|placeholder| {
+        json!({
+            "name": placeholder.name,
+            "optional": placeholder.optional,
+        })
+    });
+
+    let (description, spans) = render_tokens_plain(&entry.description);
+
+    json!({
+        "flags": entry.flags,
+        "placeholder": placeholder,
+        "tag": entry.tag_text,
+        "section": format!("{:?}", entry.section),
+        "description": styled_json(&description, &spans),
+    })
+}
+
+/// The sections of rendered output, each holding its subsections.
+fn sections_json(text: &str, spans: &[StyleSpan], headings: &[Heading]) -> Vec<Value> {
+    let mut sections: Vec<Value> = Vec::new();
+
+    for (index, heading) in headings.iter().enumerate() {
+        let end = headings
+            .get(index + 1)
+            .map(|next| next.start)
+            .unwrap_or_else(|| text.len());
+
+        let section = json!({
+            "title": heading.title,
+            "content": styled_json(&text[heading.body_start..end], &shift_spans(spans, heading.body_start, end)),
+            "subsections": [],
+        });
+
+        match sections.last_mut() {
+            Some(parent) if heading.is_subsection => {
+                parent["subsections"].as_array_mut().unwrap().push(section)
+            }
+            _ => sections.push(section),
+        }
+    }
+
+    sections
+}
+
+/// The spans within start..end, relative to start.
+fn shift_spans(spans: &[StyleSpan], start: usize, end: usize) -> Vec<StyleSpan> {
+    spans
+        .iter()
+        .filter(|span| span.start >= start && span.end <= end)
+        .map(|span| StyleSpan {
+            start: span.start - start,
+            end: span.end - start,
+            style: span.style,
+        })
+        .collect()
+}
+
+/// Plain text and the runs of it that are styled.
+/// Surrounding whitespace is trimmed, and span offsets count chars, not bytes.
+fn styled_json(text: &str, spans: &[StyleSpan]) -> Value {
+    let trimmed = text.trim();
+    let trim_start = text.len() - text.trim_start().len();
+
+    let char_offset = |byte_offset: usize| {
+        let byte_offset = byte_offset.max(trim_start).min(trim_start + trimmed.len());
+        text[trim_start..byte_offset].chars().count()
+    };
+
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            json!({
+                "start": char_offset(span.start),
+                "end": char_offset(span.end),
+                "style": style_name(span.style),
+            })
+        })
+        .filter(|span| span["start"] != span["end"])
+        .collect();
+
+    json!({
+        "text": trimmed,
+        "spans": spans,
+    })
+}
+
+fn style_name(style: FontStyle) -> &'static str {
+    match style {
+        FontStyle::Bold => "bold",
+        FontStyle::Italic => "italic",
+        FontStyle::BoldItalic => "bold-italic",
+        FontStyle::Underlined => "underline",
+        FontStyle::Regular => "regular",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use man_parse::troff_token_generator::TroffTokenGenerator;
+    use simple_parser::tokenizer::tokenize;
+
+    #[test]
+    fn test_styled_json_trims_and_counts_chars() {
+        let spans = vec![StyleSpan {
+            start: 6,
+            end: 10,
+            style: FontStyle::Bold,
+        }];
+
+        let actual = styled_json("\n  é bold \n", &spans);
+
+        assert_eq!(
+            actual,
+            json!({
+                "text": "é bold",
+                "spans": [{ "start": 2, "end": 6, "style": "bold" }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_page_sections() {
+        let page = ".SH NAME\nls \\- list\n.SH OPTIONS\n.SS Basic\n.TP\n\\fB\\-a\\fR\nall\n";
+        let tokens = tokenize(page, &TroffTokenGenerator {});
+
+        let mut parser = TroffParser::new();
+        parser.enable_plain_output();
+        parser.parse(tokens.iter());
+
+        let actual = page_json("ls.1", &parser);
+
+        assert_eq!(actual["sections"][0]["title"], "NAME");
+        assert_eq!(actual["sections"][0]["content"]["text"], "ls - list");
+        assert_eq!(actual["sections"][1]["title"], "OPTIONS");
+        assert_eq!(actual["sections"][1]["subsections"][0]["title"], "Basic");
+        assert_eq!(actual["options"][0]["flags"], json!(["-a"]));
+        assert_eq!(actual["options"][0]["description"]["text"], "all");
+    }
+
+    #[test]
+    fn test_explained_args() {
+        let page = ".SH OPTIONS\n.TP\n\\fB\\-f\\fR \\fIfile\\fR\nread \\fBfile\\fR\n";
+        let tokens = tokenize(page, &TroffTokenGenerator {});
+
+        let mut parser = TroffParser::new().with_args(vec!["-f".into(), "a.txt".into()]);
+        parser.enable_plain_output();
+        parser.parse(tokens.iter());
+
+        let actual = page_json("x.1", &parser);
+
+        assert_eq!(
+            actual["args"][0],
+            json!({
+                "arg": "-f",
+                "value": "a.txt",
+                "entry": {
+                    "flags": ["-f"],
+                    "placeholder": { "name": "file", "optional": false },
+                    "tag": "-f file",
+                    "section": "Options",
+                    "description": {
+                        "text": "read file",
+                        "spans": [{ "start": 5, "end": 9, "style": "bold" }],
+                    },
+                },
+            })
+        );
+    }
+}
@@ -1,35 +1,280 @@
+#[macro_use]
+extern crate serde_json;
+
 mod arg_parse;
+mod json_format;
+mod man_file;
 mod man_parse;
+mod shell_parse;
 mod simple_parser;
 mod text_format;
 
-use arg_parse::arg_parse::argparse;
+use arg_parse::arg_parse::{argparse, ExplainArgs, OutputFormat};
+use json_format::json_output::{command_json, page_json};
+use man_file::include::read_page_with_includes;
+use man_file::man_path::ManPath;
+use man_file::man_volume::ManVolume;
+use man_parse::macro_package::MacroPackage;
+use man_parse::man_section::ManSection;
 use man_parse::troff_parser::TroffParser;
-use std::fs::File;
-use std::io::Read;
-use std::process::Command;
+use man_parse::troff_token_generator::{tokenize_page, TroffToken};
+use shell_parse::command_line::{nested_command_start, parse_command_line, quote};
+use simple_parser::token::Token;
+use std::slice;
+use text_format::text_format::TextFormat;
+
+/// The parser of a page, as given to the output functions of explain_command()
+type PageParser<'a> = TroffParser<'a, slice::Iter<'a, Token<TroffToken>>>;
 
 fn main() {
     let args = argparse();
 
-    let man_path = get_manpage_path(&args.command_name);
+    if let Some(command_line) = shell_command_line(&args) {
+        explain_command_line(&command_line, &args);
+        return;
+    }
 
-    if args.debug {
-        println!("found manpath: [{}]", &man_path);
+    let (man_path, command_args) = match find_command_page(&args) {
+        Some(found) => found,
+        None => {
+            let message = match args.volume {
+                Some(ref volume) => format!(
+                    "No manual entry for {} in section {}",
+                    args.command_name, volume
+                ),
+                None => format!("No manual entry for {}", args.command_name),
+            };
+
+            match args.format {
+                OutputFormat::Text => println!("{}", message),
+                OutputFormat::Json => println!("{}", json!({ "error": message })),
+            }
+            ::std::process::exit(1);
+        }
+    };
+
+    let explained = match args.format {
+        OutputFormat::Text => {
+            explain_command(&man_path, command_args, &args, args.section, |parser| {
+                parser.result_text().to_owned()
+            })
+        }
+        OutputFormat::Json => {
+            explain_command(&man_path, command_args, &args, args.section, |parser| {
+                let page = page_json(&man_path, parser);
+                serde_json::to_string_pretty(&page).unwrap()
+            })
+        }
+    };
+
+    match explained {
+        Ok(text) => println!("{}", text),
+        Err(message) => {
+            match args.format {
+                OutputFormat::Text => println!("{}", message),
+                OutputFormat::Json => println!("{}", json!({ "error": message })),
+            }
+            ::std::process::exit(1);
+        }
     }
+}
 
-    let man_text = if is_gzipped(&man_path) {
-        unzip(&man_path)
-    } else {
-        read_file_content(&man_path)
+/// If we were given a whole shell command line rather than a single command,
+/// i.e. explain 'find . -name "*.rs" | xargs grep foo', returns that command line.
+/// A command that runs another, i.e. explain xargs grep -n foo, is explained as one too,
+/// so each command's args are explained against its own page.
+fn shell_command_line(args: &ExplainArgs) -> Option<String> {
+    let is_operator = |word: &str| {
+        ["|", "||", "&&", ";", "&"].contains(&word)
+            || word.starts_with('>')
+            || word.starts_with('<')
     };
 
-    // if !is_troff(&man_text) {
-    //     println!("Non-troff man content detected. Does this man page use mandoc instead?");
-    // }
+    if args.command_name.contains(char::is_whitespace) {
+        // the whole command line was given as one quoted string
+        let mut line = args.command_name.clone();
+        for arg in &args.command_args {
+            line.push(' ');
+            line.push_str(&quote(arg));
+        }
+
+        return Some(line);
+    }
+
+    let runs_command = nested_command_start(&args.command_name, &args.command_args).is_some();
+
+    if runs_command || args.command_args.iter().any(|arg| is_operator(arg)) {
+        // the operators were quoted individually, i.e. explain ls -l '|' wc -l
+        let words: Vec<String> = args
+            .command_args
+            .iter()
+            .map(|arg| {
+                if is_operator(arg) {
+                    arg.clone()
+                } else {
+                    quote(arg)
+                }
+            })
+            .collect();
+
+        return Some(format!("{} {}", quote(&args.command_name), words.join(" ")));
+    }
+
+    None
+}
+
+/// Explain each command of a shell command line in turn,
+/// along with its expansions, its redirections and how it connects to the next command.
+fn explain_command_line(command_line: &str, args: &ExplainArgs) {
+    if args.format == OutputFormat::Json {
+        explain_command_line_json(command_line, args);
+        return;
+    }
+
+    for command in parse_command_line(command_line) {
+        for (index, words) in command.command_chain().into_iter().enumerate() {
+            // a command run by the one before is shown on its own
+            if index == 0 {
+                println!("{}", command_line[command.span.clone()].bold());
+            } else {
+                let quoted: Vec<String> = words.iter().map(|word| quote(word)).collect();
+                println!("{}", quoted.join(" ").as_str().bold());
+            }
+
+            let name = &words[0];
+            match find_manpage(name, &words[1..], None) {
+                Some((man_path, subcommand_count)) => {
+                    let command_args = &words[1 + subcommand_count..];
+
+                    // without any options to explain, a summary of the command is enough
+                    let section = if command_args.iter().any(|arg| arg.starts_with('-')) {
+                        args.section
+                    } else {
+                        Some(ManSection::Name)
+                    };
+
+                    let explained =
+                        explain_command(&man_path, command_args, args, section, |parser| {
+                            parser.result_text().to_owned()
+                        });
+
+                    match explained {
+                        Ok(text) => println!("{}", text),
+                        Err(message) => println!("{}", message),
+                    }
+                }
+                None => println!("No manual entry for {}", name),
+            }
+        }
+
+        for expansion in &command.expansions {
+            println!(
+                "{}: {}",
+                command_line[expansion.span.clone()].bold(),
+                expansion.explain()
+            );
+        }
+
+        for redirection in &command.redirections {
+            println!(
+                "{}: {}",
+                redirection.to_string().as_str().bold(),
+                redirection.explain()
+            );
+        }
+
+        if let Some(separator) = command.separator {
+            println!("{}: {}", separator.operator().bold(), separator.explain());
+        }
+
+        println!();
+    }
+}
+
+/// Explain each command of a shell command line as JSON.
+fn explain_command_line_json(command_line: &str, args: &ExplainArgs) {
+    let commands: Vec<serde_json::Value> = parse_command_line(command_line)
+        .iter()
+        .map(|command| {
+            let page = |words: &[String]| {
+                let (man_path, subcommand_count) = find_manpage(&words[0], &words[1..], None)?;
+                let command_args = &words[1 + subcommand_count..];
+
+                let explained =
+                    explain_command(&man_path, command_args, args, args.section, |parser| {
+                        page_json(&man_path, parser)
+                    });
+
+                Some(explained.unwrap_or_else(|message| json!({ "error": message })))
+            };
+
+            let chain = command.command_chain();
+            let nested: Vec<serde_json::Value> = chain
+                .iter()
+                .skip(1)
+                .map(|words| json!({ "words": words, "page": page(words) }))
+                .collect();
+
+            command_json(
+                command_line,
+                command,
+                chain.first().and_then(|words| page(words)),
+                nested,
+            )
+        })
+        .collect();
+
+    let output = json!({
+        "command_line": command_line,
+        "commands": commands,
+    });
+
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Parse the man page at the given path, and hand the parser to 'output'
+/// to produce the explanation of the args,
+/// or of the whole page (or section) if there are no args to explain.
+/// Returns an error message if the page can't be read,
+/// or is written in a format we can't parse.
+fn explain_command<F, T>(
+    man_path: &str,
+    command_args: &[String],
+    args: &ExplainArgs,
+    section: Option<ManSection>,
+    output: F,
+) -> Result<T, String>
+where
+    F: for<'a> FnOnce(&PageParser<'a>) -> T,
+{
+    if args.debug {
+        println!("found manpath: [{}]", man_path);
+    }
+
+    let man_text = read_page_with_includes(man_path).map_err(|e| e.to_string())?;
+
+    let package = MacroPackage::detect(&man_text);
+
+    if args.debug {
+        println!("macro package: {}", package.name());
+    }
+
+    if package == MacroPackage::Unknown {
+        return Err(format!(
+            "The page at {} isn't a troff man page, so it can't be explained",
+            man_path
+        ));
+    }
+
+    if !package.is_supported() {
+        return Err(format!(
+            "The page at {} is written with the {} macros, which can't be explained",
+            man_path,
+            package.name()
+        ));
+    }
 
-    let classifier = man_parse::troff_token_generator::TroffTokenGenerator {};
-    let tokenized = simple_parser::tokenizer::tokenize(&man_text, &classifier);
+    let tokenized = tokenize_page(&man_text);
 
     if args.debug {
         for tok in &tokenized {
@@ -37,65 +282,110 @@ fn main() {
         }
     }
 
-    let mut parser = TroffParser::new().with_args(args.command_args);
+    let mut parser = TroffParser::new().with_args(command_args.to_vec());
     if args.debug_visualize_margin {
         parser.enable_debug();
     }
 
-    if let Some(section) = args.section {
+    if args.italics {
+        parser.enable_italics();
+    }
+
+    if args.format == OutputFormat::Json {
+        parser.enable_plain_output();
+    }
+
+    if let Some(section) = section {
         parser = parser.for_section(section);
     }
 
     parser.parse(tokenized.iter());
 
-    if args.debug && args.section.is_some() {
+    if args.debug {
+        for entry in parser.option_entries() {
+            println!(
+                "option entry: [{}] placeholder: {:?} section: {:?}",
+                entry.tag_text, entry.placeholder, entry.section
+            );
+        }
+    }
+
+    if args.debug && section.is_some() {
         println!("tokens:\n{}", parser.before_section_text());
         println!("-----------------");
     }
 
-    println!("{}", parser.result_text());
+    Ok(output(&parser))
 }
 
-fn get_manpage_path(program_name: &str) -> String {
-    let output = Command::new("man")
-        .arg("-w")
-        .arg(program_name)
-        .output()
-        .expect("failed to invoke man");
-
-    let formatted = format!("{}", String::from_utf8_lossy(&output.stdout));
+/// Find the page for the command given on the command line,
+/// and the rest of its args that are explained against that page.
+/// Like man, if there's no page in the volume taken from the command's name,
+/// the name is looked up as it was given, i.e. 'tclsh8.6' rather than 'tclsh8' in volume 6.
+fn find_command_page(args: &ExplainArgs) -> Option<(String, &[String])> {
+    let volume = args.volume.as_ref();
+    if let Some((path, subcommand_count)) =
+        find_manpage(&args.command_name, &args.command_args, volume)
+    {
+        return Some((path, &args.command_args[subcommand_count..]));
+    }
 
-    formatted.trim().into()
+    let (command_name, command_args) = args.unsplit.as_ref()?;
+    let (path, subcommand_count) = find_manpage(command_name, command_args, None)?;
+    Some((path, &command_args[subcommand_count..]))
 }
 
-fn is_gzipped(path: &str) -> bool {
-    path.ends_with(".gz")
-}
+/// The most subcommands deep we will look for a page, i.e. 'kubectl config view'
+const MAX_SUBCOMMAND_DEPTH: usize = 3;
 
-fn unzip(zip_path: &str) -> String {
-    let output = Command::new("gunzip")
-        .arg("-c")
-        .arg(zip_path)
-        .output()
-        .expect("failed to invoke gunzip");
+/// Separators used to join a command and its subcommands into a page name,
+/// i.e. 'git-commit' or 'git_commit'
+const SUBCOMMAND_SEPARATORS: &[&str] = &["-", "_"];
 
-    format!("{}", String::from_utf8_lossy(&output.stdout))
-}
+/// Find the man page for the command, preferring the page of the deepest subcommand
+/// given in its args, i.e. 'git commit --amend' finds the page for 'git-commit'.
+/// If a volume is given, only pages in that volume are considered.
+/// Returns the path to the page, and how many of the args were subcommands.
+fn find_manpage(
+    command_name: &str,
+    command_args: &[String],
+    volume: Option<&ManVolume>,
+) -> Option<(String, usize)> {
+    let manpath = ManPath::from_env();
 
-// by convention, man pages begin with ".TH"
-// (after comments, which we will presume to be preprocessed out)
-// fn is_troff(text: &str) -> bool {
-//     text.starts_with(".TH")
-// }
+    let subcommands: Vec<&String> = command_args
+        .iter()
+        .take(MAX_SUBCOMMAND_DEPTH)
+        .take_while(|arg| !arg.starts_with('-'))
+        .collect();
 
-fn read_file_content(file_path: &str) -> String {
-    let mut file = File::open(file_path).expect(&format!("path not found: {}", &file_path));
+    for depth in (1..subcommands.len() + 1).rev() {
+        for separator in SUBCOMMAND_SEPARATORS {
+            let mut page_name = command_name.to_owned();
+            for subcommand in &subcommands[..depth] {
+                page_name.push_str(separator);
+                page_name.push_str(subcommand);
+            }
 
-    let mut content = String::new();
-
-    if let Err(e) = file.read_to_string(&mut content) {
-        panic!("Could not read file: {} with error: {}", file_path, e)
+            if let Some(path) = get_manpage_path(&manpath, &page_name, volume) {
+                return Some((path, depth));
+            }
+        }
     }
 
-    content
+    get_manpage_path(&manpath, command_name, volume).map(|path| (path, 0))
+}
+
+/// The path to the named page, if there is one.
+/// If there are pages in several sections, the first in search order wins.
+fn get_manpage_path(
+    manpath: &ManPath,
+    program_name: &str,
+    volume: Option<&ManVolume>,
+) -> Option<String> {
+    manpath
+        .find_pages(program_name, volume)
+        .into_iter()
+        .next()
+        .map(|path| path.to_string_lossy().into_owned())
 }
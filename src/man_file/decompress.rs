@@ -0,0 +1,246 @@
+extern crate bzip2;
+extern crate flate2;
+extern crate lzma_rs;
+extern crate ruzstd;
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+
+/// The ways a man page file may be compressed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Lzma,
+    Zstd,
+}
+
+impl Compression {
+    /// Decide how some file content is compressed, from the magic bytes it begins with.
+    /// The extension of a page's path isn't trusted, since distros don't always agree on it.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if bytes.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if bytes.starts_with(&[0x5d, 0x00, 0x00]) {
+            // the legacy .lzma format has no magic number,
+            // but nearly every encoder writes these default properties first
+            Compression::Lzma
+        } else {
+            Compression::None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Compression::None => "uncompressed",
+            Compression::Gzip => "gzip",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+            Compression::Lzma => "lzma",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+/// An error reading a man page from disk.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The file couldn't be opened or read.
+    Io { path: String, error: io::Error },
+
+    /// The file was compressed, but its content couldn't be decompressed.
+    Decompress {
+        path: String,
+        compression: Compression,
+        message: String,
+    },
+
+    /// A '.so' request named a file that couldn't be found.
+    IncludeNotFound { path: String, include: String },
+
+    /// Pages included each other, i.e. a.1 includes b.1, which includes a.1.
+    /// The chain of includes ends with the page included a second time.
+    IncludeCycle { chain: Vec<String> },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadError::Io {
+                ref path,
+                ref error,
+            } => write!(f, "Could not read {}: {}", path, error),
+            ReadError::Decompress {
+                ref path,
+                compression,
+                ref message,
+            } => write!(
+                f,
+                "Could not decompress {} as {}: {}",
+                path,
+                compression.name(),
+                message
+            ),
+            ReadError::IncludeNotFound {
+                ref path,
+                ref include,
+            } => write!(f, "Could not find {}, included by {}", include, path),
+            ReadError::IncludeCycle { ref chain } => {
+                write!(f, "Pages include each other: {}", chain.join(" -> "))
+            }
+        }
+    }
+}
+
+/// Read the man page at the given path, decompressing it if it's compressed.
+pub fn read_page(path: &str) -> Result<String, ReadError> {
+    let mut bytes = Vec::new();
+
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|error| ReadError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+
+    let compression = Compression::detect(&bytes);
+
+    let content = decompress(&bytes, compression).map_err(|message| ReadError::Decompress {
+        path: path.to_owned(),
+        compression,
+        message,
+    })?;
+
+    Ok(decode_page(content))
+}
+
+/// Decode the text of a page, which is usually UTF-8, but older pages may be Latin-1.
+/// Every byte is a character in Latin-1, so any text that isn't valid UTF-8 is read as Latin-1.
+fn decode_page(content: Vec<u8>) -> String {
+    String::from_utf8(content).unwrap_or_else(|error| {
+        error
+            .into_bytes()
+            .iter()
+            .map(|&byte| byte as char)
+            .collect()
+    })
+}
+
+/// Decompress some bytes compressed with the given compression.
+pub fn decompress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+
+    match compression {
+        Compression::None => output.extend_from_slice(bytes),
+        Compression::Gzip => {
+            flate2::read::MultiGzDecoder::new(bytes)
+                .read_to_end(&mut output)
+                .map_err(|e| e.to_string())?;
+        }
+        Compression::Bzip2 => {
+            bzip2::read::MultiBzDecoder::new(bytes)
+                .read_to_end(&mut output)
+                .map_err(|e| e.to_string())?;
+        }
+        Compression::Xz => {
+            lzma_rs::xz_decompress(&mut &bytes[..], &mut output).map_err(|e| e.to_string())?;
+        }
+        Compression::Lzma => {
+            lzma_rs::lzma_decompress(&mut &bytes[..], &mut output).map_err(|e| e.to_string())?;
+        }
+        Compression::Zstd => {
+            ruzstd::decoding::StreamingDecoder::new(bytes)
+                .map_err(|e| e.to_string())?
+                .read_to_end(&mut output)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const PAGE: &str = ".TH LS 1\n.SH NAME\nls \\- list directory contents\n";
+
+    #[test]
+    fn test_detect_plain_text() {
+        assert_eq!(Compression::detect(PAGE.as_bytes()), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(PAGE.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(Compression::detect(&compressed), Compression::Gzip);
+        assert_eq!(
+            decompress(&compressed, Compression::Gzip).unwrap(),
+            PAGE.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_bzip2_round_trip() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(PAGE.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(Compression::detect(&compressed), Compression::Bzip2);
+        assert_eq!(
+            decompress(&compressed, Compression::Bzip2).unwrap(),
+            PAGE.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_xz_and_lzma_round_trip() {
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut PAGE.as_bytes(), &mut xz).unwrap();
+        assert_eq!(Compression::detect(&xz), Compression::Xz);
+        assert_eq!(decompress(&xz, Compression::Xz).unwrap(), PAGE.as_bytes());
+
+        let mut lzma = Vec::new();
+        lzma_rs::lzma_compress(&mut PAGE.as_bytes(), &mut lzma).unwrap();
+        assert_eq!(Compression::detect(&lzma), Compression::Lzma);
+        assert_eq!(
+            decompress(&lzma, Compression::Lzma).unwrap(),
+            PAGE.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_corrupt_content_is_an_error() {
+        let truncated = [0x1f, 0x8b, 0x08, 0x00];
+
+        assert_eq!(Compression::detect(&truncated), Compression::Gzip);
+        assert!(decompress(&truncated, Compression::Gzip).is_err());
+        assert!(decompress(b"BZh9garbage", Compression::Bzip2).is_err());
+    }
+
+    #[test]
+    fn test_decode_utf8_and_latin1() {
+        assert_eq!(
+            decode_page("Nik\u{161}i\u{107}".as_bytes().to_vec()),
+            "Nik\u{161}i\u{107}"
+        );
+        assert_eq!(
+            decode_page(b"Fran\xe7ois M\xfcller".to_vec()),
+            "Fran\u{e7}ois M\u{fc}ller"
+        );
+    }
+}
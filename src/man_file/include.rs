@@ -0,0 +1,182 @@
+use man_file::decompress::{read_page, ReadError};
+use std::path::{Path, PathBuf};
+
+/// The extensions a compressed page may have,
+/// since '.so' requests usually name the uncompressed file.
+const COMPRESSED_EXTENSIONS: &[&str] = &["gz", "bz2", "xz", "lzma", "zst"];
+
+/// Read the man page at the given path, replacing each '.so file' request
+/// with the content of that file, i.e. the page for zcat is often just '.so man1/gzip.1'.
+/// Included files are found relative to the man root (the dir above man1, man3, ...),
+/// and may include files of their own.
+pub fn read_page_with_includes(path: &str) -> Result<String, ReadError> {
+    let root = man_root(Path::new(path));
+    let mut stack = Vec::new();
+
+    read_included(Path::new(path), &root, &mut stack)
+}
+
+/// Read a page and its includes, where 'stack' is the chain of pages including it.
+fn read_included(path: &Path, root: &Path, stack: &mut Vec<PathBuf>) -> Result<String, ReadError> {
+    let identity = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+
+    if stack.contains(&identity) {
+        let mut chain: Vec<String> = stack.iter().map(|p| p.display().to_string()).collect();
+        chain.push(identity.display().to_string());
+
+        return Err(ReadError::IncludeCycle { chain });
+    }
+
+    let text = read_page(&path.to_string_lossy())?;
+
+    stack.push(identity);
+
+    let mut result = String::with_capacity(text.len());
+    for line in text.lines() {
+        match include_target(line) {
+            Some(target) => {
+                let target_path =
+                    find_include(target, root, path).ok_or_else(|| ReadError::IncludeNotFound {
+                        path: path.display().to_string(),
+                        include: target.to_owned(),
+                    })?;
+
+                let included = read_included(&target_path, root, stack)?;
+                result.push_str(&included);
+                if !included.ends_with('\n') {
+                    result.push('\n');
+                }
+            }
+            None => {
+                result.push_str(line);
+                result.push('\n');
+            }
+        }
+    }
+
+    stack.pop();
+
+    Ok(result)
+}
+
+/// The file a line includes, if the line is a '.so' request.
+fn include_target(line: &str) -> Option<&str> {
+    let rest = if line.starts_with('.') || line.starts_with('\'') {
+        line[1..].trim_start()
+    } else {
+        return None;
+    };
+
+    let mut words = rest.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some("so"), Some(target)) => Some(target),
+        _ => None,
+    }
+}
+
+/// The man root of a page, i.e. /usr/share/man for /usr/share/man/man1/ls.1.gz
+fn man_root(page: &Path) -> PathBuf {
+    page.parent()
+        .and_then(|section_dir| section_dir.parent())
+        .map(|root| root.to_owned())
+        .unwrap_or_default()
+}
+
+/// Find the file named by a '.so' request.
+/// Relative names are tried against the man root, then the including page's dir,
+/// and the file may have been compressed since the request was written.
+fn find_include(target: &str, root: &Path, including: &Path) -> Option<PathBuf> {
+    let target = Path::new(target);
+
+    let mut bases = vec![root.join(target)];
+    if let Some(dir) = including.parent() {
+        bases.push(dir.join(target));
+    }
+
+    for base in bases {
+        if base.is_file() {
+            return Some(base);
+        }
+
+        for extension in COMPRESSED_EXTENSIONS {
+            let mut compressed = base.clone().into_os_string();
+            compressed.push(".");
+            compressed.push(extension);
+
+            let compressed = PathBuf::from(compressed);
+            if compressed.is_file() {
+                return Some(compressed);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Make a man dir in the temp dir holding the given files and their content.
+    fn man_dir(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = env::temp_dir().join(format!("explain_rs_include_{}", test_name));
+        let _ = fs::remove_dir_all(&root);
+
+        for &(file, content) in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn test_include_target() {
+        assert_eq!(include_target(".so man1/gzip.1"), Some("man1/gzip.1"));
+        assert_eq!(include_target(".  so man1/gzip.1"), Some("man1/gzip.1"));
+        assert_eq!(include_target(".SH NAME"), None);
+        assert_eq!(include_target("so man1/gzip.1"), None);
+    }
+
+    #[test]
+    fn test_stub_page() {
+        let root = man_dir(
+            "stub",
+            &[
+                ("man1/zcat.1", ".so man1/gzip.1\n"),
+                ("man1/gzip.1", ".TH GZIP 1\n.so man1/common.1\n.SH NAME\n"),
+                ("man1/common.1", ".\\\" shared\n"),
+            ],
+        );
+
+        let text = read_page_with_includes(&root.join("man1/zcat.1").to_string_lossy()).unwrap();
+
+        assert_eq!(text, ".TH GZIP 1\n.\\\" shared\n.SH NAME\n");
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let root = man_dir(
+            "cycle",
+            &[
+                ("man1/a.1", ".so man1/b.1\n"),
+                ("man1/b.1", ".so man1/a.1\n"),
+            ],
+        );
+
+        match read_page_with_includes(&root.join("man1/a.1").to_string_lossy()) {
+            Err(ReadError::IncludeCycle { chain }) => assert_eq!(chain.len(), 3),
+            other => panic!("expected an include cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_missing_include() {
+        let root = man_dir("missing", &[("man1/a.1", ".so man1/gone.1\n")]);
+
+        assert!(read_page_with_includes(&root.join("man1/a.1").to_string_lossy()).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use man_file::man_volume::ManVolume;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The config files of man-db, which list where pages are installed.
+const CONFIG_PATHS: &[&str] = &["/etc/man_db.conf", "/etc/manpath.config"];
+
+/// Where pages are usually installed, if nothing tells us otherwise.
+const DEFAULT_MANPATH: &[&str] = &[
+    "/usr/local/share/man",
+    "/usr/share/man",
+    "/usr/local/man",
+    "/usr/man",
+];
+
+/// The order sections are searched in, the same as man-db's default.
+/// Commands come first, then system administration, then library functions.
+const DEFAULT_SECTION_ORDER: &[&str] =
+    &["1", "n", "l", "8", "3", "0", "2", "5", "4", "9", "6", "7"];
+
+/// What we read from man-db's config files.
+#[derive(Debug, Default, PartialEq)]
+pub struct ManConfig {
+    /// Dirs that are always searched (MANDATORY_MANPATH).
+    pub mandatory: Vec<PathBuf>,
+
+    /// Which man dir holds the pages for the commands of a bin dir (MANPATH_MAP).
+    pub path_map: Vec<(PathBuf, PathBuf)>,
+
+    /// The order sections are searched in (SECTION), if given.
+    pub sections: Vec<String>,
+}
+
+impl ManConfig {
+    /// Read the first man-db config file that exists, or an empty config if there is none.
+    pub fn from_system() -> Self {
+        CONFIG_PATHS
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .next()
+            .map(|text| ManConfig::parse(&text))
+            .unwrap_or_default()
+    }
+
+    /// Parse the text of a man_db.conf, ignoring any directives we don't use.
+    pub fn parse(text: &str) -> Self {
+        let mut config = ManConfig::default();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            match fields.as_slice() {
+                ["MANDATORY_MANPATH", dir, ..] => config.mandatory.push(dir.into()),
+                ["MANPATH_MAP", bin_dir, man_dir, ..] => {
+                    config.path_map.push((bin_dir.into(), man_dir.into()))
+                }
+                ["SECTION", sections @ ..] | ["SECTIONS", sections @ ..] => {
+                    config.sections = sections.iter().map(|s| (*s).to_owned()).collect()
+                }
+                _ => {}
+            }
+        }
+
+        config
+    }
+}
+
+/// The dirs we search for pages, in the order they're searched.
+#[derive(Debug)]
+pub struct ManPath {
+    dirs: Vec<PathBuf>,
+    sections: Vec<String>,
+}
+
+impl ManPath {
+    /// The man path of this system, from $MANPATH, $PATH and man-db's config.
+    pub fn from_env() -> Self {
+        let manpath_var = env::var("MANPATH").ok();
+        let path_var = env::var("PATH").ok();
+
+        ManPath::new(
+            manpath_var.as_deref(),
+            path_var.as_deref(),
+            &ManConfig::from_system(),
+        )
+    }
+
+    /// Build the man path the way man-db does.
+    /// If $MANPATH is set it's used as-is, except that an empty entry
+    /// (i.e. a leading, trailing or doubled ':') is replaced by the system man path.
+    /// The system man path is the man dir of each dir on $PATH,
+    /// then the config's mandatory dirs, then the usual install locations.
+    /// Only dirs that exist are kept.
+    pub fn new(manpath_var: Option<&str>, path_var: Option<&str>, config: &ManConfig) -> Self {
+        let system = system_manpath(path_var, config);
+
+        let mut dirs: Vec<PathBuf> = match manpath_var {
+            Some(manpath) if !manpath.is_empty() => {
+                let mut dirs = Vec::new();
+                for entry in manpath.split(':') {
+                    if entry.is_empty() {
+                        dirs.extend(system.iter().cloned());
+                    } else {
+                        dirs.push(entry.into());
+                    }
+                }
+                dirs
+            }
+            _ => system,
+        };
+
+        let mut seen = Vec::new();
+        dirs.retain(|dir| {
+            if seen.contains(dir) || !dir.is_dir() {
+                return false;
+            }
+            seen.push(dir.clone());
+            true
+        });
+
+        let sections = if config.sections.is_empty() {
+            DEFAULT_SECTION_ORDER
+                .iter()
+                .map(|s| (*s).to_owned())
+                .collect()
+        } else {
+            config.sections.clone()
+        };
+
+        ManPath { dirs, sections }
+    }
+
+    /// Find every page with the given name, in search order, as man-db does:
+    /// by section across the whole man path, i.e. ls(1) before ls(1p) before ls(3),
+    /// then by dir of the man path for pages in the same section.
+    /// If a volume is given, only pages in that volume are found.
+    pub fn find_pages(&self, name: &str, volume: Option<&ManVolume>) -> Vec<PathBuf> {
+        let mut found: Vec<(usize, usize, String, PathBuf)> = Vec::new();
+
+        for (dir_index, dir) in self.dirs.iter().enumerate() {
+            found.extend(
+                section_dirs(dir)
+                    .into_iter()
+                    .flat_map(|section_dir| pages_in(&section_dir, name))
+                    .filter(|(section, _)| volume.is_none_or(|volume| volume.contains(section)))
+                    .map(|(section, path)| (self.section_rank(&section), dir_index, section, path)),
+            );
+        }
+
+        found.sort();
+        found.into_iter().map(|(_, _, _, path)| path).collect()
+    }
+
+    /// Where a section comes in the search order.
+    /// A section with a suffix, like 3ssl, is searched along with its base section.
+    fn section_rank(&self, section: &str) -> usize {
+        self.sections
+            .iter()
+            .position(|s| s == section)
+            .or_else(|| {
+                self.sections
+                    .iter()
+                    .position(|s| section.starts_with(s.as_str()))
+            })
+            .unwrap_or(self.sections.len())
+    }
+}
+
+/// The man path derived from $PATH and the config, before $MANPATH is considered.
+fn system_manpath(path_var: Option<&str>, config: &ManConfig) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    for bin_dir in path_var.unwrap_or("").split(':').filter(|d| !d.is_empty()) {
+        let bin_dir = Path::new(bin_dir);
+
+        let mapped: Vec<PathBuf> = config
+            .path_map
+            .iter()
+            .filter(|(bin, _)| bin == bin_dir)
+            .map(|(_, man)| man.clone())
+            .collect();
+
+        if !mapped.is_empty() {
+            dirs.extend(mapped);
+        } else if let Some(parent) = bin_dir.parent() {
+            // i.e. /opt/foo/bin has its pages in /opt/foo/share/man or /opt/foo/man
+            dirs.push(parent.join("share").join("man"));
+            dirs.push(parent.join("man"));
+        }
+    }
+
+    dirs.extend(config.mandatory.iter().cloned());
+    dirs.extend(DEFAULT_MANPATH.iter().map(PathBuf::from));
+
+    dirs
+}
+
+/// The section dirs of a man dir, i.e. man1, man3, man1p, mann.
+fn section_dirs(man_dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(man_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| section_of_dir(name))
+                .is_some()
+        })
+        .collect();
+
+    dirs.sort();
+    dirs
+}
+
+/// The section a section dir holds, i.e. "1p" for "man1p".
+fn section_of_dir(dir_name: &str) -> Option<&str> {
+    if !dir_name.starts_with("man") {
+        return None;
+    }
+
+    let section = &dir_name["man".len()..];
+    match section.chars().next() {
+        Some(c) if c.is_ascii_digit() || c == 'n' || c == 'l' => Some(section),
+        _ => None,
+    }
+}
+
+/// The pages with the given name in a section dir, along with each page's section.
+/// A page's file name is its name, then its section, then any compression extension,
+/// i.e. ls.1.gz, or SSL_new.3ssl.gz in man3.
+fn pages_in(section_dir: &Path, name: &str) -> Vec<(String, PathBuf)> {
+    let dir_section = match section_dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .and_then(section_of_dir)
+    {
+        Some(section) => section.to_owned(),
+        None => return Vec::new(),
+    };
+
+    let entries = match fs::read_dir(section_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut pages: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            let section = page_section(&file_name, name)?;
+
+            // the section of the page has to belong in this dir, i.e. no ls.8 in man1
+            if !section.starts_with(dir_section.chars().next()?) {
+                return None;
+            }
+
+            Some((section.to_owned(), entry.path()))
+        })
+        .collect();
+
+    pages.sort();
+    pages
+}
+
+/// The section in a page's file name, if the file is a page with the given name.
+fn page_section<'a>(file_name: &'a str, name: &str) -> Option<&'a str> {
+    if !file_name.starts_with(name) {
+        return None;
+    }
+
+    let rest = &file_name[name.len()..];
+    if !rest.starts_with('.') {
+        return None;
+    }
+
+    rest[1..]
+        .split('.')
+        .next()
+        .filter(|section| !section.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    /// Make a man dir in the temp dir holding the given files.
+    fn man_dir(test_name: &str, files: &[&str]) -> PathBuf {
+        let root = env::temp_dir().join(format!("explain_rs_man_path_{}", test_name));
+        let _ = fs::remove_dir_all(&root);
+
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(path).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = ManConfig::parse(
+            "# comment\nMANDATORY_MANPATH\t\t\t/usr/man\nMANPATH_MAP\t/bin\t\t\t/usr/share/man\nMANDB_MAP\t/usr/man\t/var/cache/man/fsstnd\nSECTION\t\t1 n l 8 3 0 2 5 4 9 6 7\n",
+        );
+
+        assert_eq!(config.mandatory, vec![PathBuf::from("/usr/man")]);
+        assert_eq!(
+            config.path_map,
+            vec![(PathBuf::from("/bin"), PathBuf::from("/usr/share/man"))]
+        );
+        assert_eq!(config.sections.len(), 12);
+    }
+
+    #[test]
+    fn test_page_section() {
+        assert_eq!(page_section("ls.1.gz", "ls"), Some("1"));
+        assert_eq!(page_section("SSL_new.3ssl", "SSL_new"), Some("3ssl"));
+        assert_eq!(page_section("lsblk.8.gz", "ls"), None);
+        assert_eq!(page_section("ls", "ls"), None);
+    }
+
+    #[test]
+    fn test_find_pages_in_section_order() {
+        let root = man_dir(
+            "section_order",
+            &[
+                "man3/printf.3.gz",
+                "man1/printf.1.gz",
+                "man1p/printf.1p.gz",
+                "man1/printf.8",
+                "man1/printenv.1.gz",
+                "cat1/printf.1.gz",
+            ],
+        );
+
+        let manpath = ManPath::new(root.to_str(), None, &ManConfig::default());
+
+        assert_eq!(
+            manpath.find_pages("printf", None),
+            vec![
+                root.join("man1/printf.1.gz"),
+                root.join("man1p/printf.1p.gz"),
+                root.join("man3/printf.3.gz"),
+            ]
+        );
+        assert!(manpath.find_pages("missing", None).is_empty());
+
+        assert_eq!(
+            manpath.find_pages("printf", ManVolume::parse("3").as_ref()),
+            vec![root.join("man3/printf.3.gz")]
+        );
+        assert_eq!(
+            manpath.find_pages("printf", ManVolume::parse("1p").as_ref()),
+            vec![root.join("man1p/printf.1p.gz")]
+        );
+    }
+
+    #[test]
+    fn test_section_order_across_dirs() {
+        let first = man_dir("across_first", &["man3/printf.3"]);
+        let second = man_dir("across_second", &["man1/printf.1.gz", "man3/printf.3"]);
+
+        let manpath_var = format!("{}:{}", first.display(), second.display());
+        let manpath = ManPath::new(Some(&manpath_var), None, &ManConfig::default());
+
+        // a command in a later dir comes before a library function in an earlier one
+        assert_eq!(
+            manpath.find_pages("printf", None),
+            vec![
+                second.join("man1/printf.1.gz"),
+                first.join("man3/printf.3"),
+                second.join("man3/printf.3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_manpath_var_and_path_map() {
+        let first = man_dir("manpath_first", &["man1/explain_rs_test.1"]);
+        let mapped = man_dir("manpath_mapped", &["mann/explain_rs_test.n"]);
+
+        let config = ManConfig {
+            path_map: vec![(PathBuf::from("/explain_rs/bin"), mapped.clone())],
+            ..Default::default()
+        };
+
+        // the trailing ':' brings in the system man path, derived from $PATH
+        let manpath_var = format!("{}:", first.display());
+        let manpath = ManPath::new(Some(&manpath_var), Some("/explain_rs/bin"), &config);
+
+        assert_eq!(
+            manpath.find_pages("explain_rs_test", None),
+            vec![
+                first.join("man1/explain_rs_test.1"),
+                mapped.join("mann/explain_rs_test.n")
+            ]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+
+/// A volume of the manual, i.e. 1 for commands or 3 for library functions,
+/// along with any suffix, like the 'p' of 1p (POSIX) or the 'ssl' of 3ssl.
+/// Not to be confused with ManSection, which is a heading within a page.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ManVolume(String);
+
+impl ManVolume {
+    /// Parse a volume, i.e. "3", "3ssl" or "n".
+    /// A volume is a digit followed by an optional suffix of letters,
+    /// or one of the lettered volumes 'n' (Tcl) and 'l' (local).
+    pub fn parse(volume: &str) -> Option<Self> {
+        let mut chars = volume.chars();
+
+        let valid = match chars.next() {
+            Some(c) if c.is_ascii_digit() => chars.all(|c| c.is_ascii_alphabetic()),
+            Some('n') | Some('l') => volume.len() == 1,
+            _ => false,
+        };
+
+        if valid {
+            Some(ManVolume(volume.to_lowercase()))
+        } else {
+            None
+        }
+    }
+
+    /// Split a volume off the end of a page name, if it has one,
+    /// i.e. "printf(3)" or "printf.3" is the page "printf" in volume 3.
+    pub fn split_page_name(name: &str) -> (&str, Option<Self>) {
+        if name.ends_with(')') {
+            if let Some(open) = name.rfind('(') {
+                if let Some(volume) = ManVolume::parse(&name[open + 1..name.len() - 1]) {
+                    return (&name[..open], Some(volume));
+                }
+            }
+        }
+
+        if let Some(dot) = name.rfind('.') {
+            if dot > 0 {
+                if let Some(volume) = ManVolume::parse(&name[dot + 1..]) {
+                    return (&name[..dot], Some(volume));
+                }
+            }
+        }
+
+        (name, None)
+    }
+
+    /// True if a page of the given section belongs in this volume.
+    /// Asking for volume 3 finds pages in 3 and 3ssl, but asking for 3ssl only finds 3ssl.
+    pub fn contains(&self, section: &str) -> bool {
+        section.to_lowercase().starts_with(&self.0)
+    }
+}
+
+impl fmt::Display for ManVolume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ManVolume::parse("3"), Some(ManVolume("3".into())));
+        assert_eq!(ManVolume::parse("3SSL"), Some(ManVolume("3ssl".into())));
+        assert_eq!(ManVolume::parse("n"), Some(ManVolume("n".into())));
+        assert_eq!(ManVolume::parse("11"), None);
+        assert_eq!(ManVolume::parse("ls"), None);
+        assert_eq!(ManVolume::parse(""), None);
+    }
+
+    #[test]
+    fn test_split_page_name() {
+        assert_eq!(
+            ManVolume::split_page_name("printf(3)"),
+            ("printf", ManVolume::parse("3"))
+        );
+        assert_eq!(
+            ManVolume::split_page_name("printf.3"),
+            ("printf", ManVolume::parse("3"))
+        );
+        assert_eq!(
+            ManVolume::split_page_name("SSL_new.3ssl"),
+            ("SSL_new", ManVolume::parse("3ssl"))
+        );
+        assert_eq!(
+            ManVolume::split_page_name("python3.11"),
+            ("python3.11", None)
+        );
+        assert_eq!(ManVolume::split_page_name("printf"), ("printf", None));
+    }
+
+    #[test]
+    fn test_contains() {
+        let volume = ManVolume::parse("3").unwrap();
+        assert!(volume.contains("3"));
+        assert!(volume.contains("3ssl"));
+        assert!(!volume.contains("1"));
+
+        assert!(!ManVolume::parse("3ssl").unwrap().contains("3"));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod decompress;
+pub mod include;
+pub mod man_path;
+pub mod man_volume;
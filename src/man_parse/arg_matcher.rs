@@ -0,0 +1,279 @@
+use man_parse::option_entry::OptionEntry;
+
+/// A single option given on the command line,
+/// paired with the entry that documents it, if one was found.
+#[derive(Debug)]
+pub struct MatchedArg<'e> {
+    /// The option as it would be written on its own,
+    /// i.e. "-x" when it was given as part of "-xzvf"
+    pub arg: String,
+
+    /// The documentation for the option, if the page has it.
+    pub entry: Option<&'e OptionEntry>,
+
+    /// The value given to the option, if it takes one.
+    pub value: Option<String>,
+}
+
+/// Match command line arguments against the options a page declares,
+/// in the order the arguments were given.
+/// Clusters of short options like "-xzvf" are split into "-x", "-z", "-v", "-f".
+/// Option values are understood in any of the forms "--opt=value", "--opt value",
+/// "-o value" and "-ovalue", where the option's placeholder decides if the next argument
+/// is its value.
+/// Arguments that aren't options (and aren't option values) are skipped.
+pub fn match_args<'e>(args: &[String], entries: &'e [OptionEntry]) -> Vec<MatchedArg<'e>> {
+    let mut matched = Vec::new();
+    let mut args_iter = args.iter();
+
+    while let Some(arg) = args_iter.next() {
+        if arg == "--" {
+            // by convention, everything after '--' is an operand
+            break;
+        }
+
+        if !arg.starts_with('-') || arg == "-" {
+            continue;
+        }
+
+        // some options are spelled with a single dash and multiple letters, like 'find -name',
+        // so an exact match always wins over splitting a cluster
+        if let Some(entry) = find_entry(entries, arg) {
+            let (arg, value) = split_attached_value(arg);
+
+            let value = value.or_else(|| match entry.placeholder {
+                Some(ref placeholder) if !placeholder.optional => args_iter.next().cloned(),
+                _ => None,
+            });
+
+            matched.push(MatchedArg {
+                arg: arg.to_owned(),
+                entry: Some(entry),
+                value,
+            });
+        } else if is_short_cluster(arg) {
+            match_cluster(arg, entries, &mut args_iter, &mut matched);
+        } else {
+            let (arg, value) = split_attached_value(arg);
+
+            matched.push(MatchedArg {
+                arg: arg.to_owned(),
+                entry: None,
+                value,
+            });
+        }
+    }
+
+    matched
+}
+
+/// Split an option written like "--opt=value" into "--opt" and "value".
+/// Options without an '=' have no attached value.
+fn split_attached_value(arg: &str) -> (&str, Option<String>) {
+    match arg.find('=') {
+        Some(index) => (&arg[..index], Some(arg[index + 1..].to_owned())),
+        None => (arg, None),
+    }
+}
+
+/// Split a cluster of short options, like "-xzvf", into its individual options.
+/// An option that takes a value ends the cluster: the rest of the cluster is its value,
+/// or if nothing remains, the next argument is.
+fn match_cluster<'e, 'a, A>(
+    cluster: &str,
+    entries: &'e [OptionEntry],
+    args_iter: &mut A,
+    matched: &mut Vec<MatchedArg<'e>>,
+) where
+    A: Iterator<Item = &'a String>,
+{
+    let letters = &cluster[1..];
+
+    for (index, letter) in letters.char_indices() {
+        let flag = format!("-{}", letter);
+        let entry = find_entry(entries, &flag);
+
+        let placeholder = entry.and_then(|e| e.placeholder.as_ref());
+
+        if let Some(placeholder) = placeholder {
+            let rest = &letters[index + letter.len_utf8()..];
+
+            let value = if !rest.is_empty() {
+                Some(rest.to_owned())
+            } else if !placeholder.optional {
+                args_iter.next().cloned()
+            } else {
+                None
+            };
+
+            matched.push(MatchedArg {
+                arg: flag,
+                entry,
+                value,
+            });
+
+            return;
+        }
+
+        matched.push(MatchedArg {
+            arg: flag,
+            entry,
+            value: None,
+        });
+    }
+}
+
+/// True if the argument looks like one or more short options written together, like "-xzvf"
+fn is_short_cluster(arg: &str) -> bool {
+    arg.starts_with('-') && !arg.starts_with("--") && arg.chars().count() > 2
+}
+
+fn find_entry<'e>(entries: &'e [OptionEntry], arg: &str) -> Option<&'e OptionEntry> {
+    entries.iter().find(|entry| entry.matches(arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use man_parse::man_section::ManSection;
+
+    fn entries(tags: &[&str]) -> Vec<OptionEntry> {
+        tags.iter()
+            .map(|tag| {
+                OptionEntry::from_tagged_paragraph(tag, Vec::new(), Vec::new(), ManSection::Options)
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn summarize(matched: &[MatchedArg]) -> Vec<(String, bool, Option<String>)> {
+        matched
+            .iter()
+            .map(|m| (m.arg.clone(), m.entry.is_some(), m.value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_cluster() {
+        let entries = entries(&["-x, --extract", "-z, --gzip", "-v, --verbose"]);
+        let matched = match_args(&args(&["-xzv"]), &entries);
+
+        assert_eq!(
+            summarize(&matched),
+            vec![
+                ("-x".to_owned(), true, None),
+                ("-z".to_owned(), true, None),
+                ("-v".to_owned(), true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cluster_value_from_next_arg() {
+        let entries = entries(&["-x, --extract", "-f, --file=ARCHIVE", "-v"]);
+        let matched = match_args(&args(&["-xf", "foo.tar", "-v"]), &entries);
+
+        assert_eq!(
+            summarize(&matched),
+            vec![
+                ("-x".to_owned(), true, None),
+                ("-f".to_owned(), true, Some("foo.tar".to_owned())),
+                ("-v".to_owned(), true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cluster_value_from_rest_of_cluster() {
+        let entries = entries(&["-x, --extract", "-f, --file=ARCHIVE", "-v"]);
+        let matched = match_args(&args(&["-xffoo.tar"]), &entries);
+
+        assert_eq!(
+            summarize(&matched),
+            vec![
+                ("-x".to_owned(), true, None),
+                ("-f".to_owned(), true, Some("foo.tar".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_dash_long_option_is_not_split() {
+        let entries = entries(&["-n", "-a", "-m", "-e", "-name pattern"]);
+        let matched = match_args(&args(&["-name"]), &entries);
+
+        assert_eq!(summarize(&matched), vec![("-name".to_owned(), true, None)]);
+    }
+
+    #[test]
+    fn test_unknown_letters_in_cluster() {
+        let entries = entries(&["-x"]);
+        let matched = match_args(&args(&["-xq"]), &entries);
+
+        assert_eq!(
+            summarize(&matched),
+            vec![
+                ("-x".to_owned(), true, None),
+                ("-q".to_owned(), false, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_option_values() {
+        let entries = entries(&["--color[=WHEN]", "-o, --output=FILE", "-v"]);
+        let matched = match_args(
+            &args(&["--color=always", "--output", "out.txt", "--color", "-v"]),
+            &entries,
+        );
+
+        assert_eq!(
+            summarize(&matched),
+            vec![
+                ("--color".to_owned(), true, Some("always".to_owned())),
+                ("--output".to_owned(), true, Some("out.txt".to_owned())),
+                ("--color".to_owned(), true, None),
+                ("-v".to_owned(), true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_short_option_values() {
+        let entries = entries(&["-O[LEVEL]", "-j N", "-o FILE", "-v"]);
+        let matched = match_args(&args(&["-O2", "-j8", "-o", "-v", "-O"]), &entries);
+
+        assert_eq!(
+            summarize(&matched),
+            vec![
+                ("-O".to_owned(), true, Some("2".to_owned())),
+                ("-j".to_owned(), true, Some("8".to_owned())),
+                ("-o".to_owned(), true, Some("-v".to_owned())),
+                ("-O".to_owned(), true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_dash_long_option_value() {
+        let entries = entries(&["-std=standard"]);
+        let matched = match_args(&args(&["-std=c99"]), &entries);
+
+        assert_eq!(
+            summarize(&matched),
+            vec![("-std".to_owned(), true, Some("c99".to_owned()))]
+        );
+    }
+
+    #[test]
+    fn test_operands_are_skipped() {
+        let entries = entries(&["-v"]);
+        let matched = match_args(&args(&["foo", "-v", "--", "-v"]), &entries);
+
+        assert_eq!(summarize(&matched), vec![("-v".to_owned(), true, None)]);
+    }
+}
@@ -0,0 +1,138 @@
+/// The set of macros a page is written with.
+/// man and mdoc pages are both read by TroffParser, since their macro names don't overlap,
+/// but pages written with any other package can't be explained.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MacroPackage {
+    /// The Linux/GNU man macros: .TH, .SH, .TP, ...
+    Man,
+
+    /// The BSD semantic macros: .Dd, .Dt, .Sh, .Bl, ...
+    Mdoc,
+
+    /// The ms document macros: .TL, .NH, .AU, ...
+    Ms,
+
+    /// The me document macros: .sh, .pp, .(l, ...
+    Me,
+
+    /// The mm memorandum macros: .MT, .HU, ...
+    Mm,
+
+    /// No macros we recognize, i.e. the page is plain text or already formatted
+    Unknown,
+}
+
+impl MacroPackage {
+    /// Decide which macro package a page is written with, from the first macro
+    /// that only one package defines.
+    /// Comments and preprocessor hints (like '\" t) before it are skipped,
+    /// as are the roff requests (.de, .if, .ds, ...) that often come before a man page's .TH
+    pub fn detect(text: &str) -> Self {
+        let mut has_man_sections = false;
+
+        for line in text.lines() {
+            let name = match macro_name(line) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            match name {
+                "TH" => return MacroPackage::Man,
+                "Dd" | "Dt" | "Os" | "Sh" => return MacroPackage::Mdoc,
+                "TL" | "NH" | "AU" | "AI" | "AB" | "RP" => return MacroPackage::Ms,
+                "sh" | "pp" | "lp" | "np" | "uh" | "(l" | "(q" => return MacroPackage::Me,
+                "MT" | "HU" | "AL" => return MacroPackage::Mm,
+                "SH" | "SS" => has_man_sections = true,
+                _ => {}
+            }
+        }
+
+        // a page without .TH (i.e. one meant to be included by another) is still a man page
+        if has_man_sections {
+            MacroPackage::Man
+        } else {
+            MacroPackage::Unknown
+        }
+    }
+
+    /// True if TroffParser can read pages written with this package.
+    pub fn is_supported(&self) -> bool {
+        matches!(*self, MacroPackage::Man | MacroPackage::Mdoc)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MacroPackage::Man => "man",
+            MacroPackage::Mdoc => "mdoc",
+            MacroPackage::Ms => "ms",
+            MacroPackage::Me => "me",
+            MacroPackage::Mm => "mm",
+            MacroPackage::Unknown => "unknown",
+        }
+    }
+}
+
+/// The name of the macro called by a line, if the line calls one.
+/// Comment lines, like '.\" comment' or the preprocessor hint '\" t, call nothing.
+fn macro_name(line: &str) -> Option<&str> {
+    // the control char is either '.' or the no-break control char '\''
+    let rest = if line.starts_with('.') || line.starts_with('\'') {
+        line[1..].trim_start()
+    } else {
+        return None;
+    };
+
+    if rest.starts_with("\\\"") || rest.starts_with("\\#") {
+        return None;
+    }
+
+    rest.split_whitespace().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_man_after_comments_and_requests() {
+        let page =
+            "'\\\" t\n.\\\" Copyright\n.de Vb\n.ft CW\n..\n.if n .ds C` \"\"\n.TH LS 1\n.SH NAME\n";
+
+        assert_eq!(MacroPackage::detect(page), MacroPackage::Man);
+    }
+
+    #[test]
+    fn test_mdoc() {
+        let page = ".\\\"\t$OpenBSD$\n.Dd $Mdocdate$\n.Dt LS 1\n.Os\n.Sh NAME\n";
+
+        assert_eq!(MacroPackage::detect(page), MacroPackage::Mdoc);
+    }
+
+    #[test]
+    fn test_unsupported_packages() {
+        assert_eq!(
+            MacroPackage::detect(".TL\nA Paper\n.AU\nSomeone\n.SH\nIntro\n"),
+            MacroPackage::Ms
+        );
+        assert_eq!(
+            MacroPackage::detect(".sh 1 Intro\n.pp\ntext\n"),
+            MacroPackage::Me
+        );
+        assert!(!MacroPackage::Ms.is_supported());
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let page = "LS(1)    User Commands    LS(1)\n\nNAME\n   ls - list directory contents\n";
+
+        assert_eq!(MacroPackage::detect(page), MacroPackage::Unknown);
+    }
+
+    #[test]
+    fn test_man_fragment_without_th() {
+        assert_eq!(
+            MacroPackage::detect(".SH OPTIONS\n.TP\n\\-a\n"),
+            MacroPackage::Man
+        );
+    }
+}
@@ -1,4 +1,8 @@
+pub mod arg_matcher;
+pub mod macro_package;
 pub mod man_section;
+pub mod option_entry;
+pub mod special_chars;
 pub mod term_writer;
 pub mod troff_parser;
 pub mod troff_token_generator;
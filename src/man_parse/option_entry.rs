@@ -0,0 +1,225 @@
+use man_parse::man_section::ManSection;
+use man_parse::troff_token_generator::TroffToken;
+use simple_parser::token::Token;
+
+/// The argument an option accepts, as named in its tag.
+/// I.e., the 'ARCHIVE' in "-f, --file=ARCHIVE"
+#[derive(Debug, PartialEq, Clone)]
+pub struct Placeholder {
+    pub name: String,
+
+    /// True if the argument may be omitted, as in "--color[=WHEN]"
+    pub optional: bool,
+}
+
+/// A single option documented by a man page,
+/// extracted from a tagged paragraph such as:
+/// .TP
+/// \fB\-f\fR, \fB\-\-file\fR=\fIARCHIVE\fR
+/// Use archive file or device ARCHIVE.
+#[derive(Debug, Clone)]
+pub struct OptionEntry {
+    /// Every spelling of the option, short and long, in the order of the tag.
+    /// I.e., ["-f", "--file"]
+    pub flags: Vec<String>,
+
+    /// The argument the option takes, if any.
+    pub placeholder: Option<Placeholder>,
+
+    /// The plain text of the tag, i.e. "-f, --file=ARCHIVE"
+    pub tag_text: String,
+
+    /// The raw tokens of the tag line.
+    pub tag: Vec<Token<TroffToken>>,
+
+    /// The raw tokens of the paragraph following the tag.
+    pub description: Vec<Token<TroffToken>>,
+
+    /// The section (.SH) the option was found in.
+    pub section: ManSection,
+}
+
+impl OptionEntry {
+    /// Create an entry from the parts of a tagged paragraph.
+    /// Returns None if the tag doesn't name any options.
+    pub fn from_tagged_paragraph(
+        tag_text: &str,
+        tag: Vec<Token<TroffToken>>,
+        description: Vec<Token<TroffToken>>,
+        section: ManSection,
+    ) -> Option<Self> {
+        let (flags, placeholder) = parse_tag(tag_text);
+
+        if flags.is_empty() {
+            return None;
+        }
+
+        Some(OptionEntry {
+            flags,
+            placeholder,
+            tag_text: tag_text.to_owned(),
+            tag,
+            description,
+            section,
+        })
+    }
+
+    /// True if the given command line argument refers to this option,
+    /// either exactly or with an attached value, like "--file=foo.tar".
+    /// Single letter options never take a value with '=', since "-f=foo" means "-f" "=foo".
+    pub fn matches(&self, arg: &str) -> bool {
+        self.flags.iter().any(|flag| {
+            arg == flag
+                || (flag.chars().count() > 2
+                    && arg.starts_with(flag.as_str())
+                    && arg[flag.len()..].starts_with('='))
+        })
+    }
+
+    /// The entry as a standalone tagged paragraph, ready to be parsed:
+    /// .TP
+    /// [tag]
+    /// [description]
+    pub fn to_tokens(&self) -> Vec<Token<TroffToken>> {
+        let mut tokens = vec![Token::new(TroffToken::Macro, ".TP".into(), true)];
+
+        let mut tag = self.tag.clone();
+        if let Some(first) = tag.first_mut() {
+            // the tag may have been an argument on the macro line, as with .IP
+            first.starts_line = true;
+        }
+
+        tokens.append(&mut tag);
+        tokens.extend(self.description.iter().cloned());
+
+        tokens
+    }
+}
+
+/// Split the plain text of a tag into the option spellings it names,
+/// and the argument placeholder, if any.
+/// I.e., "-f, --file=ARCHIVE" gives ["-f", "--file"] and "ARCHIVE"
+fn parse_tag(tag: &str) -> (Vec<String>, Option<Placeholder>) {
+    let mut flags = Vec::new();
+    let mut placeholder = None;
+
+    for part in tag.split(',').map(|part| part.trim()) {
+        if !part.starts_with('-') {
+            continue;
+        }
+
+        let flag_end = part
+            .find(|c: char| c == '=' || c == '[' || c.is_whitespace())
+            .unwrap_or(part.len());
+
+        flags.push(part[..flag_end].to_owned());
+
+        if placeholder.is_none() {
+            placeholder = parse_placeholder(&part[flag_end..]);
+        }
+    }
+
+    (flags, placeholder)
+}
+
+/// Parse whatever follows an option spelling in a tag.
+/// "=FILE", " FILE" and "<file>" are required arguments,
+/// while "[=WHEN]" and "[LEVEL]" are optional.
+fn parse_placeholder(text: &str) -> Option<Placeholder> {
+    let text = text.trim();
+
+    let (inner, optional) = if text.starts_with('[') {
+        (text.trim_start_matches('[').trim_end_matches(']'), true)
+    } else {
+        (text, false)
+    };
+
+    let name = inner
+        .trim_start_matches('=')
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .trim_matches(&['<', '>', '.'][..]);
+
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(Placeholder {
+        name: name.to_owned(),
+        optional,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_with_tag(tag: &str) -> OptionEntry {
+        OptionEntry::from_tagged_paragraph(tag, Vec::new(), Vec::new(), ManSection::Options)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_short_and_long_flags() {
+        let entry = entry_with_tag("-x, --extract, --get");
+
+        assert_eq!(entry.flags, vec!["-x", "--extract", "--get"]);
+        assert_eq!(entry.placeholder, None);
+    }
+
+    #[test]
+    fn test_required_placeholder() {
+        let entry = entry_with_tag("-f, --file=ARCHIVE");
+
+        assert_eq!(entry.flags, vec!["-f", "--file"]);
+        assert_eq!(
+            entry.placeholder,
+            Some(Placeholder {
+                name: "ARCHIVE".to_owned(),
+                optional: false,
+            })
+        );
+
+        let entry = entry_with_tag("-j N");
+        assert_eq!(entry.flags, vec!["-j"]);
+        assert_eq!(entry.placeholder.unwrap().name, "N");
+    }
+
+    #[test]
+    fn test_optional_placeholder() {
+        let entry = entry_with_tag("--color[=WHEN]");
+
+        assert_eq!(entry.flags, vec!["--color"]);
+        assert_eq!(
+            entry.placeholder,
+            Some(Placeholder {
+                name: "WHEN".to_owned(),
+                optional: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_tag_without_flags() {
+        let entry = OptionEntry::from_tagged_paragraph(
+            "FILE",
+            Vec::new(),
+            Vec::new(),
+            ManSection::Description,
+        );
+
+        assert!(entry.is_none());
+    }
+
+    #[test]
+    fn test_matches_args() {
+        let entry = entry_with_tag("-f, --file=ARCHIVE");
+
+        assert!(entry.matches("-f"));
+        assert!(entry.matches("--file"));
+        assert!(entry.matches("--file=foo.tar"));
+        assert!(!entry.matches("--filename"));
+        assert!(!entry.matches("-x"));
+    }
+}
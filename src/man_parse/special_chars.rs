@@ -0,0 +1,196 @@
+use std::char;
+use std::env;
+
+/// groff's named special characters, as written in '\(em' or '\[em]',
+/// with how each is written in UTF-8, and in plain ASCII for terminals without it.
+/// See groff_char(7)
+#[rustfmt::skip]
+const GLYPHS: &[(&str, &str, &str)] = &[
+    // dashes and hyphens
+    ("em", "—", "--"), ("en", "–", "-"), ("hy", "‐", "-"), ("-", "-", "-"),
+    ("mi", "−", "-"), ("an", "⎯", "-"),
+
+    // quotes
+    ("lq", "“", "\""), ("rq", "”", "\""), ("oq", "‘", "`"), ("cq", "’", "'"),
+    ("aq", "'", "'"), ("dq", "\"", "\""), ("Bq", "„", ",,"), ("bq", "‚", ","),
+    ("Fo", "«", "<<"), ("Fc", "»", ">>"), ("fo", "‹", "<"), ("fc", "›", ">"),
+
+    // punctuation
+    ("r!", "¡", "!"), ("r?", "¿", "?"), ("bu", "•", "o"), ("ci", "○", "o"),
+    ("pc", "·", "."), ("dg", "†", "+"), ("dd", "‡", "++"), ("sc", "§", "S"),
+    ("ps", "¶", "P"), ("lh", "☜", "<="), ("rh", "☞", "=>"), ("OK", "✓", "v"),
+    ("CR", "↵", "<-'"), ("at", "@", "@"), ("sh", "#", "#"), ("%0", "‰", "%o"),
+    ("fm", "′", "'"), ("sd", "″", "''"), ("ti", "~", "~"), ("ha", "^", "^"),
+    ("sl", "/", "/"), ("rs", "\\", "\\"), ("ul", "_", "_"), ("ru", "_", "_"),
+    ("rn", "‾", "-"), ("ba", "|", "|"), ("or", "|", "|"), ("br", "│", "|"),
+    ("bb", "¦", "|"), ("bv", "⎪", "|"), ("sq", "□", "[]"), ("lB", "[", "["),
+    ("rB", "]", "]"), ("lC", "{", "{"), ("rC", "}", "}"), ("la", "⟨", "<"),
+    ("ra", "⟩", ">"),
+
+    // bracket pieces
+    ("lt", "⎧", "{"), ("lk", "⎨", "{"), ("lb", "⎩", "{"), ("rt", "⎫", "}"),
+    ("rk", "⎬", "}"), ("rb", "⎭", "}"), ("lc", "⌈", "|"), ("rc", "⌉", "|"),
+    ("lf", "⌊", "|"), ("rf", "⌋", "|"),
+
+    // symbols
+    ("co", "©", "(C)"), ("rg", "®", "(R)"), ("tm", "™", "tm"), ("de", "°", "o"),
+    ("Do", "$", "$"), ("ct", "¢", "c"), ("Eu", "€", "EUR"), ("eu", "€", "EUR"),
+    ("Ye", "¥", "Y"), ("Po", "£", "L"), ("Cs", "¤", "o"), ("Fn", "ƒ", "f"),
+    ("mc", "µ", "u"), ("Of", "ª", "a"), ("Om", "º", "o"), ("S1", "¹", "1"),
+    ("S2", "²", "2"), ("S3", "³", "3"), ("12", "½", "1/2"), ("14", "¼", "1/4"),
+    ("34", "¾", "3/4"), ("18", "⅛", "1/8"), ("38", "⅜", "3/8"), ("58", "⅝", "5/8"),
+    ("78", "⅞", "7/8"),
+
+    // arrows
+    ("->", "→", "->"), ("<-", "←", "<-"), ("<>", "↔", "<->"), ("ua", "↑", "^"),
+    ("da", "↓", "v"), ("va", "↕", "|"), ("rA", "⇒", "=>"), ("lA", "⇐", "<="),
+    ("hA", "⇔", "<=>"), ("uA", "⇑", "^"), ("dA", "⇓", "v"), ("vA", "⇕", "||"),
+
+    // math
+    ("pl", "+", "+"), ("-+", "∓", "-+"), ("+-", "±", "+-"), ("t+-", "±", "+-"),
+    ("md", "⋅", "."), ("mu", "×", "x"), ("tmu", "×", "x"), ("di", "÷", "/"),
+    ("tdi", "÷", "/"), ("f/", "⁄", "/"), ("**", "∗", "*"), ("<=", "≤", "<="),
+    (">=", "≥", ">="), ("<<", "≪", "<<"), (">>", "≫", ">>"), ("eq", "=", "="),
+    ("!=", "≠", "!="), ("==", "≡", "=="), ("ne", "≢", "!=="), ("=~", "≅", "=~"),
+    ("|=", "≃", "-~"), ("ap", "∼", "~"), ("~~", "≈", "~~"), ("~=", "≈", "~="),
+    ("pt", "∝", "oc"), ("es", "∅", "{}"), ("mo", "∈", "E"), ("nm", "∉", "!E"),
+    ("sb", "⊂", "(="), ("sp", "⊃", "=)"), ("nb", "⊄", "!(="), ("nc", "⊅", "!=)"),
+    ("ib", "⊆", "(_="), ("ip", "⊇", "=_)"), ("ca", "∩", "^"), ("cu", "∪", "U"),
+    ("/_", "∠", "/_"), ("pp", "⊥", "_|_"), ("is", "∫", "S"), ("integral", "∫", "S"),
+    ("sum", "∑", "E"), ("product", "∏", "II"), ("coproduct", "∐", "U"),
+    ("gr", "∇", "V"), ("sr", "√", "sqrt"), ("sqrt", "√", "sqrt"), ("if", "∞", "oo"),
+    ("Ah", "ℵ", "N"), ("Im", "ℑ", "I"), ("Re", "ℜ", "R"), ("wp", "℘", "p"),
+    ("pd", "∂", "d"), ("-h", "ℏ", "h"), ("hbar", "ℏ", "h"), ("no", "¬", "~"),
+    ("tno", "¬", "~"), ("AN", "∧", "^"), ("OR", "∨", "v"), ("te", "∃", "E"),
+    ("fa", "∀", "A"), ("st", "∋", "-)"), ("tf", "∴", ".:"), ("3d", "∴", ".:"),
+
+    // Greek
+    ("*a", "α", "a"), ("*b", "β", "b"), ("*g", "γ", "g"), ("*d", "δ", "d"),
+    ("*e", "ε", "e"), ("*z", "ζ", "z"), ("*y", "η", "y"), ("*h", "θ", "h"),
+    ("*i", "ι", "i"), ("*k", "κ", "k"), ("*l", "λ", "l"), ("*m", "μ", "m"),
+    ("*n", "ν", "n"), ("*c", "ξ", "c"), ("*o", "ο", "o"), ("*p", "π", "pi"),
+    ("*r", "ρ", "r"), ("*s", "σ", "s"), ("ts", "ς", "s"), ("*t", "τ", "t"),
+    ("*u", "υ", "u"), ("*f", "φ", "f"), ("*x", "χ", "x"), ("*q", "ψ", "q"),
+    ("*w", "ω", "w"), ("*A", "Α", "A"), ("*B", "Β", "B"), ("*G", "Γ", "G"),
+    ("*D", "Δ", "D"), ("*E", "Ε", "E"), ("*Z", "Ζ", "Z"), ("*Y", "Η", "Y"),
+    ("*H", "Θ", "H"), ("*I", "Ι", "I"), ("*K", "Κ", "K"), ("*L", "Λ", "L"),
+    ("*M", "Μ", "M"), ("*N", "Ν", "N"), ("*C", "Ξ", "C"), ("*O", "Ο", "O"),
+    ("*P", "Π", "P"), ("*R", "Ρ", "R"), ("*S", "Σ", "S"), ("*T", "Τ", "T"),
+    ("*U", "Υ", "U"), ("*F", "Φ", "F"), ("*X", "Χ", "X"), ("*Q", "Ψ", "Q"),
+    ("*W", "Ω", "W"), ("+h", "ϑ", "h"), ("+f", "ϕ", "f"), ("+p", "ϖ", "p"),
+    ("+e", "ϵ", "e"),
+
+    // ligatures and letters
+    ("ff", "ﬀ", "ff"), ("fi", "ﬁ", "fi"), ("fl", "ﬂ", "fl"), ("Fi", "ﬃ", "ffi"),
+    ("Fl", "ﬄ", "ffl"), ("ss", "ß", "ss"), ("ae", "æ", "ae"), ("AE", "Æ", "AE"),
+    ("oe", "œ", "oe"), ("OE", "Œ", "OE"), ("/o", "ø", "o"), ("/O", "Ø", "O"),
+    ("/l", "ł", "l"), ("/L", "Ł", "L"), ("-D", "Ð", "D"), ("Sd", "ð", "d"),
+    ("TP", "Þ", "TH"), ("Tp", "þ", "th"), (".i", "ı", "i"), (".j", "ȷ", "j"),
+    ("IJ", "Ĳ", "IJ"), ("ij", "ĳ", "ij"),
+
+    // accented letters
+    ("'a", "á", "a"), ("'e", "é", "e"), ("'i", "í", "i"), ("'o", "ó", "o"),
+    ("'u", "ú", "u"), ("'y", "ý", "y"), ("'c", "ć", "c"), ("'A", "Á", "A"),
+    ("'E", "É", "E"), ("'I", "Í", "I"), ("'O", "Ó", "O"), ("'U", "Ú", "U"),
+    ("'Y", "Ý", "Y"), ("'C", "Ć", "C"), ("`a", "à", "a"), ("`e", "è", "e"),
+    ("`i", "ì", "i"), ("`o", "ò", "o"), ("`u", "ù", "u"), ("`A", "À", "A"),
+    ("`E", "È", "E"), ("`I", "Ì", "I"), ("`O", "Ò", "O"), ("`U", "Ù", "U"),
+    ("^a", "â", "a"), ("^e", "ê", "e"), ("^i", "î", "i"), ("^o", "ô", "o"),
+    ("^u", "û", "u"), ("^A", "Â", "A"), ("^E", "Ê", "E"), ("^I", "Î", "I"),
+    ("^O", "Ô", "O"), ("^U", "Û", "U"), (":a", "ä", "a"), (":e", "ë", "e"),
+    (":i", "ï", "i"), (":o", "ö", "o"), (":u", "ü", "u"), (":y", "ÿ", "y"),
+    (":A", "Ä", "A"), (":E", "Ë", "E"), (":I", "Ï", "I"), (":O", "Ö", "O"),
+    (":U", "Ü", "U"), (":Y", "Ÿ", "Y"), ("~a", "ã", "a"), ("~n", "ñ", "n"),
+    ("~o", "õ", "o"), ("~A", "Ã", "A"), ("~N", "Ñ", "N"), ("~O", "Õ", "O"),
+    (",c", "ç", "c"), (",C", "Ç", "C"), ("oa", "å", "a"), ("oA", "Å", "A"),
+    ("vs", "š", "s"), ("vS", "Š", "S"), ("vz", "ž", "z"), ("vZ", "Ž", "Z"),
+
+    // accents on their own
+    ("a\"", "˝", "\""), ("a-", "¯", "-"), ("a.", "˙", "."), ("a^", "^", "^"),
+    ("aa", "´", "'"), ("ga", "`", "`"), ("ab", "˘", "u"), ("ac", "¸", ","),
+    ("ad", "¨", "\""), ("ah", "ˇ", "v"), ("ao", "˚", "o"), ("a~", "~", "~"),
+    ("ho", "˛", ","),
+];
+
+/// The text of the special character with the given name, i.e. "—" for 'em',
+/// or its ASCII fallback, i.e. "--", if 'ascii' is set.
+/// Besides the names in the table, a character may be named by its code point,
+/// as in 'u2014' or 'char65', or composed of several, as in 'u0065_0301' (é).
+/// Returns None if there's no such character, in which case troff writes nothing.
+pub fn special_character(name: &str, ascii: bool) -> Option<String> {
+    if let Some(&(_, utf8, fallback)) = GLYPHS.iter().find(|glyph| glyph.0 == name) {
+        let text = if ascii { fallback } else { utf8 };
+        return Some(text.to_owned());
+    }
+
+    let text = if let Some(code_points) = name.strip_prefix('u') {
+        code_points
+            .split('_')
+            .map(|hex| u32::from_str_radix(hex, 16).ok().and_then(char::from_u32))
+            .collect::<Option<String>>()?
+    } else if let Some(decimal) = name.strip_prefix("char") {
+        decimal.parse().ok().and_then(char::from_u32)?.to_string()
+    } else {
+        return None;
+    };
+
+    if ascii {
+        Some(ascii_fallback(&text))
+    } else {
+        Some(text)
+    }
+}
+
+/// Write text given by code point in ASCII, as well as possible:
+/// using the table's fallback if it has the character, and otherwise dropping
+/// combining accents, so that 'u0065_0301' is written "e".
+fn ascii_fallback(text: &str) -> String {
+    if let Some(&(_, _, fallback)) = GLYPHS.iter().find(|glyph| glyph.1 == text) {
+        return fallback.to_owned();
+    }
+
+    text.chars()
+        .filter(|c| !('\u{300}'..='\u{36f}').contains(c))
+        .map(|c| if c.is_ascii() { c } else { '?' })
+        .collect()
+}
+
+/// True if the terminal's locale can show UTF-8, as decided by the first of
+/// LC_ALL, LC_CTYPE and LANG that is set. Without a locale, only ASCII is safe.
+pub fn locale_is_utf8() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_characters() {
+        assert_eq!(special_character("em", false), Some("—".to_owned()));
+        assert_eq!(special_character("em", true), Some("--".to_owned()));
+        assert_eq!(special_character("*p", false), Some("π".to_owned()));
+        assert_eq!(special_character("->", true), Some("->".to_owned()));
+        assert_eq!(special_character("nonsense", false), None);
+    }
+
+    #[test]
+    fn test_code_points() {
+        assert_eq!(special_character("u2014", false), Some("—".to_owned()));
+        assert_eq!(special_character("u2014", true), Some("--".to_owned()));
+        assert_eq!(
+            special_character("u0065_0301", false),
+            Some("e\u{301}".to_owned())
+        );
+        assert_eq!(special_character("u0065_0301", true), Some("e".to_owned()));
+        assert_eq!(special_character("char65", false), Some("A".to_owned()));
+        assert_eq!(special_character("uZZZZ", false), None);
+    }
+}
@@ -1,4 +1,4 @@
-use text_format::text_format::TextFormat;
+use text_format::text_format::{ItalicStyle, TextStyle};
 
 /// Simple struct holding state
 /// for font styling: bold, italic, underlined
@@ -7,23 +7,73 @@ pub struct FontStyleState {
     bold: bool,
     italic: bool,
     underlined: bool,
+
+    /// The font before the last font change, which '\fP' returns to
+    previous_font: Font,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum FontStyle {
     Bold,
     Italic,
+    BoldItalic,
     Underlined,
     Regular,
 }
 
-const SPACE: &str = " ";
+/// A font, as selected by '\fB', '\f(BI', '\f[CR]' or '.ft I'.
+/// A terminal has no constant-width fonts of its own,
+/// so those are the same as the fonts they're styled like, i.e. 'CB' is bold.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum Font {
+    #[default]
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl Font {
+    /// The font with the given name or position (1 to 4), if it's one we know.
+    pub fn from_name(name: &str) -> Option<Font> {
+        let font = match name {
+            "1" | "R" | "C" | "CW" | "CR" | "HR" | "TR" => Font::Regular,
+            "2" | "I" | "CI" | "HI" | "TI" => Font::Italic,
+            "3" | "B" | "CB" | "HB" | "TB" => Font::Bold,
+            "4" | "BI" | "CBI" | "HBI" | "TBI" => Font::BoldItalic,
+            _ => return None,
+        };
+
+        Some(font)
+    }
+
+    fn is_bold(self) -> bool {
+        self == Font::Bold || self == Font::BoldItalic
+    }
+
+    fn is_italic(self) -> bool {
+        self == Font::Italic || self == Font::BoldItalic
+    }
+}
+
+/// A run of styled text within plain, unstyled output,
+/// as byte offsets into that output.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StyleSpan {
+    pub start: usize,
+    pub end: usize,
+    pub style: FontStyle,
+}
 
 impl FontStyleState {
     pub fn set_fontstyle_value(&mut self, s: FontStyle, val: bool) {
         match s {
             FontStyle::Bold => self.bold = val,
             FontStyle::Italic => self.italic = val,
+            FontStyle::BoldItalic => {
+                self.bold = val;
+                self.italic = val;
+            }
             FontStyle::Underlined => self.underlined = val,
 
             // Regular is a special case, you can't toggle it.
@@ -36,24 +86,83 @@ impl FontStyleState {
         }
     }
 
-    pub fn stylize_text(&self, text: &str) -> Option<String> {
-        if text == SPACE {
-            // don't stylize emtpy space
-            return None;
+    /// The current font, as bold and italic make it.
+    pub fn font(&self) -> Font {
+        match (self.bold, self.italic) {
+            (true, true) => Font::BoldItalic,
+            (true, false) => Font::Bold,
+            (false, true) => Font::Italic,
+            (false, false) => Font::Regular,
         }
+    }
 
-        if self.bold {
-            return Some(text.bold());
-        }
+    /// Change to the given font, remembering the current one as the previous font.
+    pub fn set_font(&mut self, font: Font) {
+        self.previous_font = self.font();
+
+        self.bold = font.is_bold();
+        self.italic = font.is_italic();
+    }
 
-        if self.italic {
-            return Some(text.italic());
+    /// Change back to the previous font, as troff does for '\fP'.
+    /// The current font becomes the previous one, so changing back twice is no change at all.
+    pub fn set_previous_font(&mut self) {
+        self.set_font(self.previous_font);
+    }
+
+    /// The style that stylize_text() would apply, if any.
+    pub fn active_style(&self) -> Option<FontStyle> {
+        if self.bold && self.italic {
+            Some(FontStyle::BoldItalic)
+        } else if self.bold {
+            Some(FontStyle::Bold)
+        } else if self.italic {
+            Some(FontStyle::Italic)
+        } else if self.underlined {
+            Some(FontStyle::Underlined)
+        } else {
+            None
         }
+    }
+
+    /// The terminal style that text in this font style is written with,
+    /// where italics are shown as the given italic style.
+    pub fn text_style(&self, italics: ItalicStyle) -> TextStyle {
+        let italic = self.italic && italics == ItalicStyle::Italic;
+        let underline = self.underlined || (self.italic && italics == ItalicStyle::Underline);
 
-        if self.underlined {
-            return Some(text.underlined());
+        TextStyle {
+            bold: self.bold,
+            italic,
+            underline,
+            ..Default::default()
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_previous_font() {
+        let mut state = FontStyleState::default();
+
+        state.set_font(Font::Bold);
+        state.set_font(Font::Italic);
+        state.set_previous_font();
+        assert_eq!(state.font(), Font::Bold);
+
+        state.set_previous_font();
+        assert_eq!(state.font(), Font::Italic);
+    }
 
-        None
+    #[test]
+    fn test_font_names() {
+        assert_eq!(Font::from_name("BI"), Some(Font::BoldItalic));
+        assert_eq!(Font::from_name("CR"), Some(Font::Regular));
+        assert_eq!(Font::from_name("CB"), Some(Font::Bold));
+        assert_eq!(Font::from_name("2"), Some(Font::Italic));
+        assert_eq!(Font::from_name("unknown"), None);
     }
 }
@@ -1,3 +1,5 @@
+use text_format::text_format::display_width;
+
 #[derive(Default)]
 pub struct LineInfo {
     whitespace_len: usize,
@@ -19,7 +21,8 @@ impl LineInfo {
         }
     }
 
-    /// Increase the line length information based on the content of the slice argument.
+    /// Increase the line length information based on the content of the slice argument,
+    /// by the width it's displayed at.
     /// Note: currently only the first char in the word is considered when judging between
     /// whitespace and non-whitespace, so the word is expected to contain exclusively one or the other,
     /// and not a mix.
@@ -31,9 +34,9 @@ impl LineInfo {
         let first_char = word.chars().next().unwrap();
 
         if first_char.is_whitespace() {
-            self.whitespace_len += word.len();
+            self.whitespace_len += display_width(word);
         } else {
-            self.nonwhitespace_len += word.len();
+            self.nonwhitespace_len += display_width(word);
         }
     }
 
@@ -1,8 +1,10 @@
 extern crate term_size;
 
-use man_parse::term_writer::font_style::{FontStyle, FontStyleState};
+use man_parse::term_writer::font_style::{Font, FontStyle, FontStyleState, StyleSpan};
 use man_parse::term_writer::line_info::{LengthRule, LineInfo};
 use std::cmp;
+use std::mem;
+use text_format::text_format::{display_width, Color, ItalicStyle, TextStyle};
 
 const DEFAULT_LINE_LENGTH: usize = 80;
 const RIGHT_MARGIN_LENGTH: usize = 8;
@@ -64,6 +66,28 @@ pub struct TroffTermWriter {
     /// When this exceeds line_length, we will wrap.
     cur_line_info: LineInfo,
 
+    /// When true, text is written without terminal styling,
+    /// and the styled runs are recorded in style_spans instead.
+    plain: bool,
+
+    /// The styled runs of the output, when writing plain output.
+    style_spans: Vec<StyleSpan>,
+
+    /// How italic text is shown on the terminal.
+    italics: ItalicStyle,
+
+    /// The colors of the text, set with '\m' and '\M'
+    foreground: Option<Color>,
+    background: Option<Color>,
+
+    /// The style of the text at the end of output_buf,
+    /// so escapes are only written when the style changes.
+    written_style: TextStyle,
+
+    /// When true, the next text is added without wrapping,
+    /// since it follows an unbreakable space.
+    no_break: bool,
+
     debug: bool,
 }
 
@@ -83,6 +107,40 @@ impl TroffTermWriter {
         self.debug = false;
     }
 
+    /// Write text without terminal styling, recording where styles apply instead.
+    /// See style_spans().
+    pub fn enable_plain(&mut self) {
+        self.plain = true;
+    }
+
+    /// Show italic text as the given style, either true italics or underlining.
+    pub fn set_italic_style(&mut self, italics: ItalicStyle) {
+        self.italics = italics;
+    }
+
+    pub fn italic_style(&self) -> ItalicStyle {
+        self.italics
+    }
+
+    pub fn foreground(&self) -> Option<Color> {
+        self.foreground
+    }
+
+    /// Set the color of the text that follows, or the terminal's default color for None.
+    pub fn set_foreground(&mut self, color: Option<Color>) {
+        self.foreground = color;
+    }
+
+    pub fn background(&self) -> Option<Color> {
+        self.background
+    }
+
+    /// Set the background color of the text that follows,
+    /// or the terminal's default background for None.
+    pub fn set_background(&mut self, color: Option<Color>) {
+        self.background = color;
+    }
+
     /// Clear bold/italic/underlined properties
     pub fn reset_font_properties(&mut self) {
         self.font_style = Default::default();
@@ -125,6 +183,37 @@ impl TroffTermWriter {
         self.margin -= amount;
     }
 
+    /// The distance from the left of the page where text can begin.
+    pub fn margin(&self) -> usize {
+        self.margin
+    }
+
+    /// The maximum length in characters of a line, before it is wrapped.
+    pub fn max_line_length(&self) -> usize {
+        self.max_line_length
+    }
+
+    /// The length of the current line so far, including its leading whitespace.
+    pub fn line_len(&self) -> usize {
+        self.cur_line_info.len(LengthRule::Everything)
+    }
+
+    /// True if the output is empty, or ends with a blank line
+    /// (ignoring the whitespace of the current line).
+    pub fn ends_with_blank_line(&self) -> bool {
+        let mut lines = self.output_buf.rsplit(LINEBREAK);
+
+        let current = lines.next().unwrap_or("");
+        let previous = lines.next();
+
+        current.trim().is_empty() && previous.is_none_or(|line| line.trim().is_empty())
+    }
+
+    /// How many margin increases are currently in effect.
+    pub fn margin_depth(&self) -> usize {
+        self.margin_stack.len()
+    }
+
     pub fn enable_nofill(&mut self) {
         self.nofill = true;
     }
@@ -147,6 +236,16 @@ impl TroffTermWriter {
         self.font_style.set_fontstyle_value(s, false);
     }
 
+    /// Change to a font, such as BoldItalic, as '\f' and '.ft' do.
+    pub fn set_font(&mut self, font: Font) {
+        self.font_style.set_font(font);
+    }
+
+    /// Change back to the font before the last font change.
+    pub fn set_previous_font(&mut self) {
+        self.font_style.set_previous_font();
+    }
+
     /// Add some text to the output buffer, inserting linebreaks
     /// if the given text exceeds the limit.
     /// It's expected that text contains no linebreaks on its own.
@@ -156,8 +255,12 @@ impl TroffTermWriter {
             return;
         }
 
-        // TODO: need to not count zero-width chars (and count >1 width chars?)
-        if self.cur_line_info.len(LengthRule::Everything) + text.len() > self.max_line_length {
+        let no_break = mem::replace(&mut self.no_break, false);
+
+        if !no_break
+            && self.cur_line_info.len(LengthRule::Everything) + display_width(text)
+                > self.max_line_length
+        {
             self.add_linebreak();
 
             if text == SPACE {
@@ -165,19 +268,87 @@ impl TroffTermWriter {
             }
         }
 
-        self.cur_line_info.increase_len(&text);
+        self.write_text(text);
+    }
 
-        if let Some(stylized) = self.font_style.stylize_text(text) {
-            self.output_buf.push_str(&stylized);
-        } else {
-            self.output_buf.push_str(text);
+    /// Add a space the line won't be broken at, keeping the text either side together.
+    pub fn add_unbreakable_space(&mut self) {
+        self.write_text(SPACE);
+        self.no_break = true;
+    }
+
+    /// Write text to the current line, as-is.
+    fn write_text(&mut self, text: &str) {
+        let indenting = self.is_curline_whitespace_only() && text.trim().is_empty();
+        self.cur_line_info.increase_len(text);
+
+        if self.plain {
+            self.add_plain_to_buf(text);
+            return;
         }
+
+        let mut style = self.font_style.text_style(self.italics);
+        style.foreground = self.foreground;
+        style.background = self.background;
+
+        if indenting {
+            // the whitespace before a line's text is left unstyled
+            style = TextStyle::default();
+        } else if text == SPACE {
+            // an underline would show beneath a space, so spaces are never underlined
+            style.underline = false;
+        }
+
+        self.change_style(style);
+        self.output_buf.push_str(text);
+    }
+
+    /// Write the escape that changes the style of the text that follows, if it's a change.
+    fn change_style(&mut self, style: TextStyle) {
+        let transition = self.written_style.transition_to(&style);
+        self.output_buf.push_str(&transition);
+        self.written_style = style;
+    }
+
+    /// Return to the terminal's default style, so the output's styles don't carry on past it.
+    /// Every line ends in the default style, so this is only needed at the end of the output.
+    pub fn end_style(&mut self) {
+        self.change_style(TextStyle::default());
+    }
+
+    /// Add text without styling, extending the style spans to cover it.
+    fn add_plain_to_buf(&mut self, text: &str) {
+        let start = self.output_buf.len();
+        self.output_buf.push_str(text);
+        let end = self.output_buf.len();
+
+        let style = match self.font_style.active_style() {
+            Some(style) if text != SPACE => style,
+            _ => return,
+        };
+
+        if let Some(last) = self.style_spans.last_mut() {
+            // a single space between two words of the same style doesn't break the span
+            let gap = &self.output_buf[last.end..start];
+            if last.style == style && (gap.is_empty() || gap == SPACE) {
+                last.end = end;
+                return;
+            }
+        }
+
+        self.style_spans.push(StyleSpan { start, end, style });
     }
 
     pub fn buf(&self) -> &str {
         &self.output_buf
     }
 
+    /// The styled runs of the output, as offsets into buf().
+    /// Only recorded when plain output is enabled.
+    pub fn style_spans(&self) -> &[StyleSpan] {
+        &self.style_spans
+    }
+
     /// Set the indent to be used when adding lines
     /// (line breaks will also respect the indent)
     pub fn set_indent(&mut self, count: usize) {
@@ -210,6 +381,7 @@ impl TroffTermWriter {
     }
 
     pub fn add_linebreak(&mut self) {
+        self.end_style();
         self.output_buf.push_str(LINEBREAK);
         self.cur_line_info.reset();
 
@@ -246,6 +418,27 @@ impl TroffTermWriter {
         }
     }
 
+    /// Like set_whitespace_to_startpos(), except the whitespace may also decrease,
+    /// for when the margin or indent has moved left since the line began.
+    pub fn reset_whitespace_to_startpos(&mut self) {
+        if !self.is_curline_whitespace_only() {
+            return;
+        }
+
+        let line_start = self.output_buf.rfind(LINEBREAK).map_or(0, |i| i + 1);
+
+        if self.output_buf[line_start..].chars().all(|c| c == ' ') {
+            self.output_buf.truncate(line_start);
+            self.cur_line_info.reset();
+        }
+
+        if !self.debug {
+            self.set_whitespace_to_startpos();
+        } else {
+            self.set_whitespace_to_startpos_debug_visualize_margin();
+        }
+    }
+
     fn set_whitespace_to_startpos_debug_visualize_margin(&mut self) {
         assert!(
             self.is_curline_whitespace_only(),
@@ -1,11 +1,30 @@
+use man_parse::arg_matcher::match_args;
 use man_parse::man_section::ManSection;
-use man_parse::term_writer::font_style::FontStyle;
+use man_parse::option_entry::OptionEntry;
+use man_parse::special_chars::{locale_is_utf8, special_character};
+use man_parse::term_writer::font_style::{Font, FontStyle, StyleSpan};
 use man_parse::term_writer::troff_term_writer::TroffTermWriter;
+use man_parse::troff_parser::expression::eval_indent;
+use man_parse::troff_parser::macros::strip_comment;
 use man_parse::troff_token_generator::TroffToken;
 use simple_parser::token::Token;
+use std::collections::{HashMap, VecDeque};
+use text_format::text_format::{Color, ColorDepth, ItalicStyle};
+
+mod colors;
+mod conditionals;
+mod expression;
+mod interpolation;
+mod macros;
+mod mdoc;
+mod registers;
 
 const SPACE: &str = " ";
 
+/// The distance between tab stops, in characters.
+/// troff's default stops are every half inch, and nroff writes ten characters an inch.
+const TAB_WIDTH: usize = 5;
+
 pub struct TroffParser<'a, I>
 where
     // TODO: I shouldn't be the iterator, it should be a trait,
@@ -13,7 +32,42 @@ where
     I: Iterator<Item = &'a Token<TroffToken>>,
 {
     tokens: Option<I>,
-    current_token: Option<&'a Token<TroffToken>>,
+    current_token: Option<Token<TroffToken>>,
+
+    /// True once any interpolation beginning at the current token has been expanded.
+    current_token_expanded: bool,
+
+    /// Tokens to be parsed before any more are taken from 'tokens',
+    /// such as the contents of an interpolated string.
+    pending_tokens: VecDeque<Token<TroffToken>>,
+
+    /// Strings defined with .ds and .as, by name.
+    strings: HashMap<String, String>,
+
+    /// How many interpolations have been done so far.
+    interpolations: usize,
+
+    /// Macros defined with .de and .am, by name, holding their bodies as written.
+    macros: HashMap<String, String>,
+
+    /// True if special characters, like '\(em', are written in ASCII rather than UTF-8.
+    ascii_glyphs: bool,
+
+    /// Number registers defined with .nr, by name.
+    registers: HashMap<String, registers::Register>,
+
+    /// For each .ie yet to be matched by an .el, whether the .el applies.
+    else_conditions: Vec<bool>,
+
+    /// Colors defined with .defcolor, by name.
+    colors: HashMap<String, Color>,
+
+    /// How many colors the terminal can show, which defined colors are shown as closely as they can be.
+    color_depth: ColorDepth,
+
+    /// The colors that '\m[]' and '\M[]' return to, most recent last.
+    foreground_colors: Vec<Option<Color>>,
+    background_colors: Vec<Option<Color>>,
     current_section: Option<ManSection>,
 
     /// if a section was requested via '-s', store its text here
@@ -31,9 +85,154 @@ where
     /// The arguments to search for in the man page, if any.
     args: Option<Vec<String>>,
 
+    /// Every option documented by a tagged paragraph (.TP or .IP) seen so far.
+    option_entries: Vec<OptionEntry>,
+
+    /// The tagged paragraph currently being parsed, if any.
+    current_paragraph: Option<TaggedParagraph>,
+
+    /// When set, all text sent to output is also captured here, unstyled.
+    /// Used to record the text of a paragraph's tag.
+    tag_capture: Option<String>,
+
+    /// The rendered explanation of the args, once parsing is done.
+    explanation: Option<String>,
+
+    /// Each arg that was explained, with the entry explaining it.
+    explained_args: Vec<ExplainedArg>,
+
+    /// Every section and subsection heading written to output, in order.
+    headings: Vec<Heading>,
+
+    /// The name of the page's subject, as first given to the mdoc macro .Nm
+    mdoc_name: Option<String>,
+
+    /// The mdoc lists (.Bl) and displays (.Bd) currently open, innermost last.
+    mdoc_blocks: Vec<mdoc::MdocBlock>,
+
+    /// True after a '\c', or a '\' ending a line,
+    /// so that the next line continues the current one without a space.
+    continued_line: bool,
+
     debug: bool,
 }
 
+/// A paragraph beginning with a tag, such as an option entry:
+/// .TP
+/// \fB\-x\fR, \fB\-\-extract\fR
+/// Extract files from an archive.
+struct TaggedParagraph {
+    /// The plain text of the tag, i.e. "-x, --extract"
+    tag_text: String,
+
+    /// Every token of the paragraph, starting with the .TP/.IP macro itself.
+    tokens: Vec<Token<TroffToken>>,
+
+    /// The range within 'tokens' holding the tag.
+    tag_start: usize,
+    tag_end: usize,
+
+    /// True if the tag was given as an argument on the macro line, as with .IP,
+    /// rather than on its own line, as with .TP
+    tag_is_macro_arg: bool,
+
+    /// How many margins were pushed when the paragraph began.
+    /// A .PP or .RE at this depth or shallower ends the paragraph.
+    margin_depth: usize,
+
+    /// How many mdoc lists and displays were open when the paragraph began.
+    /// An .It or .El at this depth or shallower ends the paragraph.
+    list_depth: usize,
+
+    /// The section the paragraph appears in.
+    section: ManSection,
+}
+
+impl TaggedParagraph {
+    /// Convert to an OptionEntry, if the tag names any options.
+    fn into_option_entry(self) -> Option<OptionEntry> {
+        // a .TP followed directly by another .TP never reached its tag
+        if self.tag_end < self.tag_start {
+            return None;
+        }
+
+        let tag_is_macro_arg = self.tag_is_macro_arg;
+        let mut tag: Vec<Token<TroffToken>> = self.tokens[self.tag_start..self.tag_end]
+            .iter()
+            // quotes around a macro argument only group it, they are not part of the tag
+            .filter(|tok| !tag_is_macro_arg || tok.class != TroffToken::DoubleQuote)
+            .cloned()
+            .collect();
+
+        // an mdoc tag like '.It Fl a Ar file' calls macros from its first word,
+        // so on a line of its own, the tag must begin with the macro: '.Fl a Ar file'
+        let is_mdoc_item = self.tokens.first().is_some_and(|tok| tok.value == ".It");
+
+        if let Some(first) = tag.first_mut() {
+            if is_mdoc_item
+                && first.class == TroffToken::TextWord
+                && mdoc::is_callable(&first.value)
+            {
+                *first = Token::new(TroffToken::Macro, format!(".{}", first.value), true);
+            }
+        }
+
+        // the description begins on the line after the tag
+        let mut description: Vec<Token<TroffToken>> = self.tokens[self.tag_end..]
+            .iter()
+            .skip_while(|tok| !tok.starts_line)
+            .cloned()
+            .collect();
+
+        // a DocBook paragraph indents its description with .RS and .RE, which .TP does itself
+        if description.first().is_some_and(|tok| tok.value == ".RS") {
+            let body_start = description
+                .iter()
+                .skip(1)
+                .position(|tok| tok.starts_line)
+                .map_or(description.len(), |index| index + 1);
+            description.drain(..body_start);
+
+            let last_line = description.iter().rposition(|tok| tok.starts_line);
+            if let Some(last_line) = last_line {
+                if description[last_line].value == ".RE" {
+                    description.truncate(last_line);
+                }
+            }
+        }
+
+        OptionEntry::from_tagged_paragraph(&self.tag_text, tag, description, self.section)
+    }
+}
+
+/// An arg given on the command line, along with what the page says about it.
+#[derive(Debug, Clone)]
+pub struct ExplainedArg {
+    /// The option as it would be written on its own, i.e. "-x" or "--file"
+    pub arg: String,
+
+    /// The value given to the option, if it takes one.
+    pub value: Option<String>,
+
+    /// The page's entry for the option, if it has one.
+    pub entry: Option<OptionEntry>,
+}
+
+/// A section (.SH) or subsection (.SS) heading, and where it is in the output.
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub title: String,
+
+    /// True for a subsection heading (.SS)
+    pub is_subsection: bool,
+
+    /// The offset in the output where the heading itself begins.
+    pub start: usize,
+
+    /// The offset in the output where the text under the heading begins.
+    pub body_start: usize,
+}
+
 impl<'a, I> TroffParser<'a, I>
 where
     I: Iterator<Item = &'a Token<TroffToken>>,
@@ -42,11 +241,32 @@ where
         TroffParser {
             tokens: Default::default(),
             current_token: Default::default(),
+            current_token_expanded: false,
+            pending_tokens: Default::default(),
+            strings: interpolation::predefined_strings(),
+            interpolations: 0,
+            macros: Default::default(),
+            ascii_glyphs: !locale_is_utf8(),
+            registers: Default::default(),
+            else_conditions: Vec::new(),
+            colors: Default::default(),
+            color_depth: ColorDepth::detect(),
+            foreground_colors: Vec::new(),
+            background_colors: Vec::new(),
             current_section: Default::default(),
             before_section_text: Default::default(),
             parse_section: Default::default(),
             term_writer: TroffTermWriter::new(),
             args: Default::default(),
+            option_entries: Default::default(),
+            current_paragraph: Default::default(),
+            tag_capture: Default::default(),
+            explanation: Default::default(),
+            explained_args: Default::default(),
+            headings: Default::default(),
+            mdoc_name: Default::default(),
+            mdoc_blocks: Default::default(),
+            continued_line: false,
             debug: false,
         }
     }
@@ -56,6 +276,19 @@ where
         self.term_writer.enable_debug();
     }
 
+    /// Show italic text in true italics, rather than underlined.
+    pub fn enable_italics(&mut self) {
+        self.term_writer.set_italic_style(ItalicStyle::Italic);
+    }
+
+    /// Write output without terminal styling, for consumers other than a terminal.
+    /// Where styles apply is available from style_spans() instead.
+    /// Special characters are always written in UTF-8, whatever the terminal's locale.
+    pub fn enable_plain_output(&mut self) {
+        self.term_writer.enable_plain();
+        self.ascii_glyphs = false;
+    }
+
     pub fn for_section(mut self, section: ManSection) -> Self {
         self.parse_section = Some(section);
         self
@@ -75,6 +308,78 @@ where
         while self.current_token().is_some() {
             self.parse_token();
         }
+
+        self.end_tagged_paragraph();
+        self.term_writer.end_style();
+
+        if self.is_explaining_args() {
+            self.explain_args();
+        }
+    }
+
+    /// True if we were given args to explain,
+    /// in which case only the matching parts of the page are output.
+    fn is_explaining_args(&self) -> bool {
+        match self.args {
+            Some(ref args) => args.iter().any(|arg| arg.starts_with('-')),
+            None => false,
+        }
+    }
+
+    /// Find the option entry for each arg, in the order the args were given,
+    /// and render them as the explanation.
+    fn explain_args(&mut self) {
+        let mut explanation = String::new();
+        let mut explained: Vec<&OptionEntry> = Vec::new();
+        let mut explained_args = Vec::new();
+
+        for matched in match_args(self.args.as_ref().unwrap(), &self.option_entries) {
+            match matched.entry {
+                Some(entry) => {
+                    if explained.iter().any(|e| ::std::ptr::eq(*e, entry)) {
+                        continue;
+                    }
+                    explained.push(entry);
+
+                    let mut tokens = entry.to_tokens();
+
+                    // show the value the option was given, i.e. "ARCHIVE = foo.tar",
+                    // as the first line beneath the tag
+                    if let Some(ref value) = matched.value {
+                        let name = match entry.placeholder {
+                            Some(ref placeholder) => placeholder.name.as_str(),
+                            None => "value",
+                        };
+
+                        let after_tag = 1 + entry.tag.len();
+                        tokens.splice(after_tag..after_tag, value_tokens(name, value));
+                    }
+
+                    let rendered = render_tokens_with(&tokens, self.term_writer.italic_style());
+                    explanation.push_str(rendered.trim_start_matches('\n').trim_end());
+
+                    explained_args.push(ExplainedArg {
+                        arg: matched.arg,
+                        value: matched.value,
+                        entry: Some(entry.clone()),
+                    });
+                }
+                None => {
+                    explanation.push_str(&format!("{}: no explanation found", matched.arg));
+
+                    explained_args.push(ExplainedArg {
+                        arg: matched.arg,
+                        value: matched.value,
+                        entry: None,
+                    });
+                }
+            }
+
+            explanation.push_str("\n\n");
+        }
+
+        self.explanation = Some(explanation.trim_end().to_owned());
+        self.explained_args = explained_args;
     }
 
     fn parse_token(&mut self) {
@@ -84,9 +389,11 @@ where
             return;
         };
 
-        if self.term_writer.is_nofill() && tok.starts_line {
+        if self.term_writer.is_nofill() && tok.starts_line && !self.continued_line {
             match tok.class {
-                TroffToken::TextWord | TroffToken::Whitespace => self.add_linebreak_single(),
+                TroffToken::TextWord | TroffToken::Whitespace | TroffToken::Backslash => {
+                    self.add_linebreak_single()
+                }
                 _ => {}
             }
         }
@@ -106,6 +413,17 @@ where
     fn parse_macro(&mut self) {
         if let Some(tok) = self.current_token() {
             assert_eq!(tok.class, TroffToken::Macro);
+
+            // macros defined by the page take precedence, as in troff
+            if self.macros.contains_key(&tok.value[1..]) {
+                self.expand_macro();
+                return;
+            }
+
+            if self.ends_tagged_paragraph(&tok.value) {
+                self.end_tagged_paragraph();
+            }
+
             match tok.value.as_str() {
                 ".SH" => self.parse_sh(),
                 ".SS" => self.parse_ss(),
@@ -113,6 +431,7 @@ where
                 ".br" => self.parse_br(),
                 ".nf" => self.parse_nf(),
                 ".fi" => self.parse_fi(),
+                ".ft" => self.parse_ft(),
                 ".TP" => self.parse_tp(),
                 ".PD" => self.parse_pd(),
                 ".B" => self.parse_b(),
@@ -122,22 +441,143 @@ where
                 ".IP" => self.parse_ip(),
                 ".RS" => self.parse_rs(),
                 ".RB" => self.parse_rb(),
+                ".BR" => self.parse_br_alternation(),
+                ".RI" => self.parse_ri(),
+                ".IB" => self.parse_ib(),
                 ".RE" => self.parse_re(),
-                ".if" => self.parse_if(),
+                ".if" | ".ie" => self.parse_if(),
+                ".el" => self.parse_else(),
+                ".ds" | ".as" => self.parse_ds(),
+                ".de" | ".am" => self.parse_de(),
+                ".rm" => self.parse_rm(),
+                ".rn" => self.parse_rn(),
+                ".nr" => self.parse_nr(),
+                ".rr" => self.parse_rr(),
+                ".defcolor" => self.parse_defcolor(),
                 ".PP" | ".LP" | ".P" => self.parse_p(),
-                _ => {
+
+                // mdoc macros, see mdoc.rs
+                ".Sh" => self.parse_sh(),
+                ".Ss" => self.parse_ss(),
+                ".Pp" => self.parse_p(),
+                ".Dd" | ".Dt" | ".Os" => self.parse_mdoc_prologue(),
+                ".Nd" => self.parse_nd(),
+                ".Bl" => self.parse_bl(),
+                ".It" => self.parse_it(),
+                ".El" => self.parse_el(),
+                ".Bd" => self.parse_bd(),
+                ".Ed" => self.parse_ed(),
+                m if mdoc::is_callable(&m[1..]) => self.parse_mdoc_line(),
+                m => {
                     // TODO: remove this, uneeded
                     self.add_to_before_output(&format!(
                         "[skipping unknown macro: {:?}]",
                         tok.value
                     ));
 
-                    self.consume();
+                    // requests (by convention, lowercase) we don't know
+                    // are ignored along with their args, the same as troff does
+                    if m[1..].starts_with(|c: char| c.is_ascii_lowercase()) {
+                        self.consume();
+                        self.consume_line();
+                    } else {
+                        self.consume();
+                    }
                 }
             }
         }
     }
 
+    /// True if the given macro ends the current tagged paragraph.
+    fn ends_tagged_paragraph(&self, macro_name: &str) -> bool {
+        let (paragraph_depth, list_depth) = match self.current_paragraph {
+            Some(ref p) => (p.margin_depth, p.list_depth),
+            None => return false,
+        };
+
+        match macro_name {
+            // items of a list nested within the paragraph still belong to it
+            ".It" | ".El" => self.mdoc_blocks.len() <= list_depth,
+
+            ".SH" | ".SS" | ".Sh" | ".Ss" | ".TP" | ".IP" => true,
+
+            // paragraphs nested deeper than the tagged paragraph still belong to it
+            ".PP" | ".LP" | ".P" | ".Pp" | ".RE" => {
+                self.term_writer.margin_depth() <= paragraph_depth
+            }
+            _ => false,
+        }
+    }
+
+    /// Begin recording a new tagged paragraph.
+    /// The current token is expected to be the .TP/.IP macro.
+    fn begin_tagged_paragraph(&mut self) {
+        self.end_tagged_paragraph();
+
+        self.current_paragraph = Some(TaggedParagraph {
+            tag_text: String::new(),
+            tokens: Vec::new(),
+            tag_start: 0,
+            tag_end: 0,
+            tag_is_macro_arg: false,
+            margin_depth: self.term_writer.margin_depth(),
+            list_depth: self.mdoc_blocks.len(),
+            section: self.current_section.unwrap_or(ManSection::Unknown),
+        });
+    }
+
+    /// Finish recording the current tagged paragraph, if there is one,
+    /// keeping it as an OptionEntry if it documents an option.
+    fn end_tagged_paragraph(&mut self) {
+        if let Some(paragraph) = self.current_paragraph.take() {
+            if let Some(entry) = paragraph.into_option_entry() {
+                self.option_entries.push(entry);
+            }
+        }
+    }
+
+    /// Start capturing the tag of the current tagged paragraph.
+    /// All text sent to output is captured until end_tag_capture().
+    fn begin_tag_capture(&mut self) {
+        self.tag_capture = Some(String::new());
+
+        if let Some(ref mut paragraph) = self.current_paragraph {
+            paragraph.tag_start = paragraph.tokens.len();
+        }
+    }
+
+    /// Stop capturing output text, and record it as the current paragraph's tag.
+    fn end_tag_capture(&mut self) {
+        let captured = self.tag_capture.take().unwrap_or_default();
+
+        if let Some(ref mut paragraph) = self.current_paragraph {
+            paragraph.tag_text = captured.trim().to_owned();
+            paragraph.tag_end = paragraph.tokens.len();
+        }
+    }
+
+    /// Each arg that was explained, in the order given.
+    /// Empty unless there were args to explain.
+    pub fn explained_args(&self) -> &[ExplainedArg] {
+        &self.explained_args
+    }
+
+    /// The styled runs of the output, when plain output is enabled.
+    pub fn style_spans(&self) -> &[StyleSpan] {
+        self.term_writer.style_spans()
+    }
+
+    /// The section and subsection headings of the output, in order.
+    pub fn headings(&self) -> &[Heading] {
+        &self.headings
+    }
+
+    /// Every option documented by the page, in the order they appear.
+    /// Available once parsing is done.
+    pub fn option_entries(&self) -> &[OptionEntry] {
+        &self.option_entries
+    }
+
     /// parse all tokens until the end of the line,
     /// so that the resulting current token is the first of the next line.
     fn parse_line(&mut self) {
@@ -191,7 +631,23 @@ where
 
     /// .P, .PP, or .LP (all mutual aliases)
     /// Adds a full line break.  Also resets indentation and font to initial values.
+    /// Pages generated from DocBook, like git's, write a tagged paragraph as a paragraph
+    /// whose first line is the tag, followed by the description indented with .RS:
+    /// .PP
+    /// \-\-amend
+    /// .RS 4
+    /// Replace the tip of the current branch...
+    /// .RE
     fn parse_p(&mut self) {
+        let is_tagged = match self.peek_line_starts(2).as_slice() {
+            [tag, indent] => tag.class != TroffToken::Macro && indent.value == ".RS",
+            _ => false,
+        };
+
+        if is_tagged {
+            self.begin_tagged_paragraph();
+        }
+
         self.consume();
 
         self.term_writer.zero_indent();
@@ -200,6 +656,12 @@ where
         if !self.term_writer.is_curline_whitespace_only() {
             self.add_blank_line();
         }
+
+        if is_tagged {
+            self.begin_tag_capture();
+            self.parse_line();
+            self.end_tag_capture();
+        }
     }
 
     /// .PD [Distance]
@@ -235,15 +697,15 @@ where
     /// The line after that is the paragraph text, with "indent" indentation.
     /// If the label is smaller than the indent, the paragraph begins on the same line (not implemented).
     fn parse_tp(&mut self) {
+        self.begin_tagged_paragraph();
         self.consume_val(".TP");
 
         // optional argument specifies indentation of paragraph text
         let paragraph_indent = {
             let indent_arg = self.parse_macro_arg().into_iter().next();
-            if let Some(arg) = indent_arg {
-                arg.value.parse::<usize>().unwrap()
-            } else {
-                self.term_writer.stored_or_default_indent()
+            match indent_arg.and_then(|arg| eval_indent(&arg.value)) {
+                Some(indent) => indent,
+                None => self.term_writer.stored_or_default_indent(),
             }
         };
 
@@ -251,13 +713,28 @@ where
         self.term_writer.zero_indent();
         self.add_linebreak_single();
         self.consume_spaces();
+
+        // a .TP followed directly by a macro that ends it, like another .TP, has no tag
+        if let Some(tok) = self.current_token() {
+            if tok.class == TroffToken::Macro && self.ends_tagged_paragraph(&tok.value) {
+                return;
+            }
+        }
+
+        self.begin_tag_capture();
         self.parse_line();
+        self.end_tag_capture();
 
         // now parse the paragraph line
         self.term_writer.set_indent(paragraph_indent);
         self.term_writer.store_indent();
         self.add_linebreak_single();
         self.parse_line();
+
+        // as after any text line, the next line's words are separated from it by a space
+        if !self.term_writer.is_curline_whitespace_only() {
+            self.add_to_output(SPACE);
+        }
     }
 
     /// .IP [marker [width]]\n[body]
@@ -265,6 +742,7 @@ where
     /// 'marker' is the tag, like a bullet point \[bu]
     /// 'width' is the indentation of the body from (including) the marker.
     fn parse_ip(&mut self) {
+        self.begin_tagged_paragraph();
         self.consume_val(".IP");
 
         // zero indent so tag starts at margin
@@ -276,19 +754,20 @@ where
         self.consume_spaces();
 
         // first optional arg is the marker (aka tag), it is printed flush with the margin
-        let marker_arg = self.parse_macro_arg();
-        for tok in marker_arg {
-            // todo: we should be calling parse_textword on these somehow...
-            self.add_to_output(&tok.value);
+        self.begin_tag_capture();
+        self.parse_macro_arg_inline();
+        self.end_tag_capture();
+
+        if let Some(ref mut paragraph) = self.current_paragraph {
+            paragraph.tag_is_macro_arg = true;
         }
 
         self.consume_spaces();
 
         // next optional arg is the width to indent for the paragraph
         let indent_tok = self.parse_macro_arg().into_iter().next();
-        if let Some(tok) = indent_tok {
-            let f_val = tok.value.parse::<f32>().unwrap_or(0f32);
-            self.term_writer.set_indent(f_val as usize);
+        if let Some(indent) = indent_tok.and_then(|tok| eval_indent(&tok.value)) {
+            self.term_writer.set_indent(indent);
             self.term_writer.store_indent();
         } else {
             let indent = self.term_writer.stored_or_default_indent();
@@ -315,10 +794,9 @@ where
         let margin_increase = {
             let indent_arg = self.parse_macro_arg().into_iter().next();
 
-            if let Some(arg) = indent_arg {
-                arg.value.parse::<usize>().unwrap()
-            } else {
-                self.term_writer.stored_or_default_indent()
+            match indent_arg.and_then(|arg| eval_indent(&arg.value)) {
+                Some(indent) => indent,
+                None => self.term_writer.stored_or_default_indent(),
             }
         };
 
@@ -335,12 +813,10 @@ where
 
         let decrease_arg = self.parse_macro_arg().into_iter().next();
 
-        let mut pops = if let Some(tok) = decrease_arg {
-            tok.value.parse::<usize>().unwrap()
-        } else {
-            // if no arg provided, just pop once
-            1
-        };
+        // if no arg provided, just pop once
+        let pops = decrease_arg
+            .and_then(|tok| tok.value.parse::<usize>().ok())
+            .unwrap_or(1);
 
         for _ in 0..pops {
             self.term_writer.pop_margin();
@@ -353,7 +829,7 @@ where
     /// Parse the next arg for a macro.
     /// Note that a single arg can span multiple whitespaces,
     /// if wrapped in a quote like "this is one arg".  It would be four without the quotes.
-    fn parse_macro_arg(&mut self) -> Vec<I::Item> {
+    fn parse_macro_arg(&mut self) -> Vec<Token<TroffToken>> {
         self.consume_spaces();
 
         let empty = Vec::new();
@@ -376,11 +852,39 @@ where
         }
     }
 
+    /// Like parse_macro_arg(), except the arg is parsed and output
+    /// the same as running text, so escapes like '\fB' take effect.
+    fn parse_macro_arg_inline(&mut self) {
+        self.consume_spaces();
+
+        let quoted = match self.current_token() {
+            Some(tok) if !tok.starts_line => tok.class == TroffToken::DoubleQuote,
+            _ => return,
+        };
+
+        if quoted {
+            self.consume_class(TroffToken::DoubleQuote);
+        }
+
+        while let Some(tok) = self.current_token() {
+            if tok.starts_line || (!quoted && tok.class == TroffToken::Whitespace) {
+                return;
+            }
+
+            if quoted && tok.class == TroffToken::DoubleQuote {
+                self.consume_class(TroffToken::DoubleQuote);
+                return;
+            }
+
+            self.parse_word();
+        }
+    }
+
     /// When the current token is a doublequote,
     /// return a vector of every token between
     /// this doublequote and an ending doublequote on the same line.
     /// (returns early if a newline is encountered before a closing doublequote)
-    fn get_within_quotes(&mut self) -> Vec<I::Item> {
+    fn get_within_quotes(&mut self) -> Vec<Token<TroffToken>> {
         self.consume_class(TroffToken::DoubleQuote);
 
         let mut result = Vec::new();
@@ -465,11 +969,7 @@ where
 
             if tok.class == TroffToken::DoubleQuote {
                 // quotes can group together tokens that will all have the same styling
-                let tok_group = self.get_within_quotes();
-                for t in tok_group {
-                    // todo: probably have to skip spaces
-                    self.add_to_output(&t.value);
-                }
+                self.parse_macro_arg_inline();
             } else {
                 // otherwise, we just parse a single word
                 self.parse_word();
@@ -484,6 +984,25 @@ where
         self.parse_alternation(FontStyle::Bold, FontStyle::Italic);
     }
 
+    /// Alternates between bold and regular.
+    /// (named to avoid confusion with .br, the linebreak macro)
+    fn parse_br_alternation(&mut self) {
+        self.consume_val(".BR");
+        self.parse_alternation(FontStyle::Bold, FontStyle::Regular);
+    }
+
+    /// Alternates between regular and italic.
+    fn parse_ri(&mut self) {
+        self.consume_val(".RI");
+        self.parse_alternation(FontStyle::Regular, FontStyle::Italic);
+    }
+
+    /// Alternates between italic and bold.
+    fn parse_ib(&mut self) {
+        self.consume_val(".IB");
+        self.parse_alternation(FontStyle::Italic, FontStyle::Bold);
+    }
+
     fn parse_whitespace(&mut self) {
         let space_tok = self.current_token().unwrap();
         self.add_to_output(&space_tok.value);
@@ -498,12 +1017,27 @@ where
         self.term_writer.enable_nofill();
     }
 
-    /// Ends no-fill mode.
+    /// Ends no-fill mode, and add a linebreak.
     fn parse_fi(&mut self) {
         self.consume();
+        self.add_linebreak_single();
         self.term_writer.disable_nofill();
     }
 
+    /// Request: .ft [font]
+    /// Change to the given font, or back to the previous font without one.
+    fn parse_ft(&mut self) {
+        self.consume();
+
+        let line = self.parse_request_line();
+        let name = strip_comment(&line)
+            .split_whitespace()
+            .next()
+            .unwrap_or("P");
+
+        self.change_font(name);
+    }
+
     /// Adds a linebreak.
     fn parse_br(&mut self) {
         self.consume();
@@ -518,9 +1052,21 @@ where
         self.consume_val("\\");
 
         if let Some(tok) = self.current_token() {
+            // a '\' ending a line joins it to the next
+            if tok.starts_line {
+                self.continued_line = true;
+                return;
+            }
+
+            // '\ ' is an unbreakable space
+            if tok.class == TroffToken::Whitespace {
+                self.parse_escaped_space();
+                return;
+            }
+
             match tok.value.as_str() {
                 "-" => self.parse_hyphen(),
-                "(" => self.parse_special_character(),
+                "(" | "[" | "C" => self.parse_special_character(),
                 "f" => {
                     self.parse_font_format();
 
@@ -530,13 +1076,69 @@ where
                         }
                     }
                 }
-                "m" => self.parse_color_format(),
+                "m" | "M" => self.parse_color_format(),
                 "s" => self.parse_point_size(),
+                "e" | "E" | "\\" => self.parse_escaped_text("\\"),
+                "." => self.parse_escaped_text("."),
+                // a space the width of a digit
+                "0" => self.parse_escaped_text(SPACE),
+                "~" => {
+                    self.consume();
+                    self.add_unbreakable_space();
+                }
+                "'" => self.parse_escaped_glyph("aa"),
+                "`" => self.parse_escaped_glyph("ga"),
+                "t" => self.parse_tab(SPACE),
+                // a leader, which is a tab filled with dots
+                "a" => self.parse_tab("."),
+                "c" => {
+                    self.consume();
+                    self.continued_line = true;
+                }
+                // '\&' (a zero-width character), '\%' and '\:' (break points)
+                // and '\|' and '\^' (thin spaces) are all written as nothing
                 _ => self.consume(),
             }
         }
     }
 
+    /// Escapes like \e or \. give a literal char.
+    fn parse_escaped_text(&mut self, text: &str) {
+        self.consume();
+        self.add_to_output(text);
+    }
+
+    /// Escapes like \' give a special character, the same as \(aa
+    fn parse_escaped_glyph(&mut self, name: &str) {
+        self.consume();
+
+        if let Some(text) = special_character(name, self.ascii_glyphs) {
+            self.add_to_output(&text);
+        }
+    }
+
+    /// '\ ' escapes only the first of the spaces after it.
+    fn parse_escaped_space(&mut self) {
+        let spaces = self.current_token().unwrap().value;
+        self.consume();
+
+        self.add_unbreakable_space();
+        if spaces.len() > 1 {
+            self.add_to_output(&spaces[1..]);
+        }
+    }
+
+    /// \t (or \a, a leader) moves to the next tab stop, filling the gap.
+    /// Tab stops are counted from the indent.
+    fn parse_tab(&mut self, fill: &str) {
+        self.consume();
+
+        let start = self.term_writer.margin() + self.term_writer.indent();
+        let column = self.term_writer.line_len().saturating_sub(start);
+
+        self.add_to_output(&fill.repeat(TAB_WIDTH - column % TAB_WIDTH));
+    }
+
     fn parse_point_size(&mut self) {
         self.consume_val("s");
 
@@ -551,67 +1153,81 @@ where
         self.add_to_output("-");
     }
 
-    /// \(cq gives "'"
+    /// \(em, \[em] and \C'em' give "—"
+    /// See special_chars.rs for every character.
     fn parse_special_character(&mut self) {
-        self.consume_val("(");
-        if let Some(tok) = self.current_token() {
-            match tok.value.as_str() {
-                "cq" => self.add_to_output("'"),
-                // also lq, rq, oq apparently, TODO
-                _ => {}
-            }
+        let command = self.current_token().unwrap().value;
+        self.consume();
 
+        let name = match self.current_token() {
+            Some(tok) if !tok.starts_line => tok.value,
+            _ => return,
+        };
+        self.consume();
+
+        let name = match command.as_str() {
+            "C" => strip_delimiters(&name).to_owned(),
+            _ => name,
+        };
+
+        if command == "["
+            && self.current_token().map(|tok| tok.class) == Some(TroffToken::ArgCloseBracket)
+        {
             self.consume();
         }
+
+        if let Some(text) = special_character(&name, self.ascii_glyphs) {
+            self.add_to_output(&text);
+        }
     }
 
+    /// Escape: \fx, \f(xx or \f[name]
+    /// Change font, where the font is a name like 'BI' or a position like '3'.
     fn parse_font_format(&mut self) {
         self.consume_val("f");
-        if let Some(tok) = self.current_token() {
-            // next arg must be the formatting choice
-            match tok.value.as_str() {
-                "B" => self.term_writer.set_fontstyle(FontStyle::Bold),
-                "I" => self.term_writer.set_fontstyle(FontStyle::Italic),
-                "R" | "P" => self.term_writer.reset_font_properties(),
-                _ => {}
-            }
 
-            self.consume();
-        }
+        let name = self.parse_escape_name();
+        self.change_font(&name);
     }
 
-    fn parse_color_format(&mut self) {
-        self.consume_val("m");
-        if let Some(tok) = self.current_token() {
-            // we now have an argument pattern
-            // \mc
-            // \m(co
-            // \m[color]
-            match tok.value.as_str() {
-                "[" => {
-                    // consume the '['
-                    self.consume_val("[");
-
-                    if let Some(inner_tok) = self.current_token() {
-                        if inner_tok.value == "]" {
-                            // next token can either close with no arg
-                            self.consume_val("]");
-                        } else {
-                            // or else it is the arg and then closes
-                            self.consume();
-                            self.consume_val("]");
-                        }
-                    }
+    /// Change to the named font. 'P', or no name at all, is the previous font.
+    fn change_font(&mut self, name: &str) {
+        match name {
+            "P" | "" => self.term_writer.set_previous_font(),
+            _ => {
+                if let Some(font) = Font::from_name(name) {
+                    self.term_writer.set_font(font);
                 }
-                "(" => {
-                    self.consume_val("(");
-                    self.consume();
-                }
-                _ => self.consume(),
             }
         }
     }
 
+    /// Parse the name an escape takes, in any of its three forms: 'x', '(xx' or '[name]'.
+    fn parse_escape_name(&mut self) -> String {
+        let open = match self.current_token() {
+            Some(tok) if !tok.starts_line => tok.class,
+            _ => return String::new(),
+        };
+
+        if open == TroffToken::ArgOpenParen || open == TroffToken::ArgOpenBracket {
+            self.consume();
+        }
+
+        let name = match self.current_token() {
+            Some(tok) if !tok.starts_line && tok.class == TroffToken::CommandArg => tok.value,
+            _ => return String::new(),
+        };
+        self.consume();
+
+        if open == TroffToken::ArgOpenBracket
+            && self.current_token().map(|tok| tok.class) == Some(TroffToken::ArgCloseBracket)
+        {
+            self.consume();
+        }
+
+        name
+    }
+
     /// .sp [LineCount]
     /// Adds some amount of spacing lines,
     /// either from an optional argument or a default amount.
@@ -640,29 +1256,22 @@ where
     /// example: ".SH OPTIONS"
     /// or
     /// ".SH\nOPTIONS"
+    /// (mdoc's .Sh is the same)
     fn parse_sh(&mut self) {
-        self.consume_val(".SH");
+        self.consume_class(TroffToken::Macro);
 
-        let mut arg_str = String::new();
+        let arg_str = self.parse_heading_args();
 
-        loop {
-            let arg = self.parse_macro_arg();
-            if arg.len() == 0 {
-                break;
-            }
-
-            arg.into_iter().for_each(|a| arg_str.push_str(&a.value));
-        }
+        // the current section is always tracked, since option entries record it
+        self.current_section = match arg_str.as_str() {
+            "NAME" => Some(ManSection::Name),
+            "SYNOPSIS" => Some(ManSection::Synopsis),
+            "DESCRIPTION" => Some(ManSection::Description),
+            "OPTIONS" => Some(ManSection::Options),
+            _ => Some(ManSection::Unknown),
+        };
 
-        if self.parse_section.is_some() {
-            self.current_section = match arg_str.as_str() {
-                "NAME" => Some(ManSection::Name),
-                "SYNOPSIS" => Some(ManSection::Synopsis),
-                "DESCRIPTION" => Some(ManSection::Description),
-                "OPTIONS" => Some(ManSection::Options),
-                _ => Some(ManSection::Unknown),
-            };
-        } else {
+        if self.parse_section.is_none() {
             // output the subheader with zero indent in bold
             self.term_writer.zero_margin();
             self.term_writer.zero_indent();
@@ -673,6 +1282,8 @@ where
                 self.add_linebreak();
             }
 
+            let start = self.term_writer.buf().len();
+
             self.term_writer.set_fontstyle(FontStyle::Bold);
             self.add_to_output(&arg_str);
             self.term_writer.reset_font_properties();
@@ -680,34 +1291,66 @@ where
             self.term_writer.default_margin();
             self.term_writer.zero_indent();
             self.add_linebreak_single();
+
+            self.headings.push(Heading {
+                title: arg_str,
+                is_subsection: false,
+                start,
+                body_start: self.term_writer.buf().len(),
+            });
         }
     }
 
     /// Parse "sub section" macro
     /// Similar to "sub header" .SH,
     /// except doesn't print flush-left.
+    /// (mdoc's .Ss is the same)
     fn parse_ss(&mut self) {
-        self.consume_val(".SS");
+        self.consume_class(TroffToken::Macro);
 
         //self.term_writer.zero_indent();
         self.add_blank_line();
 
-        let arg = self.parse_macro_arg();
+        let title = self.parse_heading_args();
+        let start = self.term_writer.buf().len();
 
         self.term_writer.set_fontstyle(FontStyle::Bold);
-        for tok in arg {
-            self.add_to_output(&tok.value);
-        }
+        self.add_to_output(&title);
         self.term_writer.reset_font_properties();
+
+        if self.section_matches() {
+            self.headings.push(Heading {
+                title,
+                is_subsection: true,
+                start,
+                body_start: self.term_writer.buf().len(),
+            });
+        }
     }
 
-    /// we aren't smart enough to evaluate expressions
-    /// so "if" will simply always be ignored
-    fn parse_if(&mut self) {
-        self.consume_val(".if");
-        self.consume_line();
+    /// The title of a heading, from all the args of its macro.
+    /// Its words may be given as separate args, i.e. '.SH SEE ALSO' rather than '.SH "SEE ALSO"'
+    fn parse_heading_args(&mut self) -> String {
+        let mut title = String::new();
+
+        loop {
+            let arg = self.parse_macro_arg();
+            if arg.is_empty() {
+                break;
+            }
+
+            if !title.is_empty() {
+                title.push(' ');
+            }
+
+            arg.into_iter().for_each(|a| title.push_str(&a.value));
+        }
+
+        title
     }
 
+    /// we aren't smart enough to evaluate expressions
+    /// so "if" will simply always be ignored
     /// consume until the beginning of the next line
     fn consume_line(&mut self) {
         while let Some(tok) = self.current_token() {
@@ -723,7 +1366,7 @@ where
         self.consume_class(TroffToken::DoubleQuote);
     }
 
-    fn format_token(token: I::Item) -> String {
+    fn format_token(token: &Token<TroffToken>) -> String {
         let val = match token.class {
             TroffToken::Whitespace => " ",
             TroffToken::EmptyLine => "[el]",
@@ -747,15 +1390,51 @@ where
     /// Consume the current token, pushing forward the iterator
     /// to the next token.
     fn consume(&mut self) {
-        self.current_token = self.tokens.as_mut().unwrap().next();
+        if let Some(tok) = self.current_token.take() {
+            self.add_to_before_output(&Self::format_token(&tok));
+
+            if let Some(ref mut paragraph) = self.current_paragraph {
+                paragraph.tokens.push(tok);
+            }
+        }
+
+        self.current_token = self.next_raw_token();
+        self.current_token_expanded = false;
+    }
+
+    /// The first token of each of the next lines after the current token's, up to 'count' lines,
+    /// looking ahead without consuming anything.
+    fn peek_line_starts(&mut self, count: usize) -> Vec<Token<TroffToken>> {
+        let mut starts = Vec::new();
+        let mut index = 0;
+
+        while starts.len() < count {
+            if index == self.pending_tokens.len() {
+                match self.tokens.as_mut().unwrap().next() {
+                    Some(tok) => self.pending_tokens.push_back(tok.clone()),
+                    None => break,
+                }
+            }
+
+            if self.pending_tokens[index].starts_line {
+                starts.push(self.pending_tokens[index].clone());
+            }
 
-        if let Some(tok) = self.current_token {
-            self.add_to_before_output(&Self::format_token(tok));
+            index += 1;
         }
+
+        starts
+    }
+
+    /// The next token, as written, from the pending tokens and then the input.
+    fn next_raw_token(&mut self) -> Option<Token<TroffToken>> {
+        self.pending_tokens
+            .pop_front()
+            .or_else(|| self.tokens.as_mut().unwrap().next().cloned())
     }
 
     fn consume_val(&mut self, it: &str) {
-        assert!(it == self.current_token.unwrap().value);
+        assert!(it == self.current_token.as_ref().unwrap().value);
         self.consume();
     }
 
@@ -769,8 +1448,19 @@ where
         }
     }
 
-    fn current_token(&self) -> Option<I::Item> {
-        self.current_token
+    /// The current token, with any interpolation (like a string) it begins expanded.
+    fn current_token(&mut self) -> Option<Token<TroffToken>> {
+        if !self.current_token_expanded {
+            self.expand_current_token();
+        }
+
+        self.current_token.clone()
+    }
+
+    /// The current token as written, without expanding any interpolation it begins.
+    /// Requests that read their args in copy mode, like .ds, use this.
+    fn current_raw_token(&self) -> Option<Token<TroffToken>> {
+        self.current_token.clone()
     }
 
     pub fn before_section_text(&self) -> &str {
@@ -778,7 +1468,10 @@ where
     }
 
     pub fn result_text(&self) -> &str {
-        self.term_writer.buf()
+        match self.explanation {
+            Some(ref explanation) => explanation,
+            None => self.term_writer.buf(),
+        }
     }
 
     fn section_matches(&self) -> bool {
@@ -815,25 +1508,274 @@ where
 
     /// Add a single linebreak (i.e. a newline \\n)
     fn add_linebreak(&mut self) {
+        self.continued_line = false;
+
         if self.section_matches() {
             self.term_writer.add_linebreak();
         }
     }
 
     fn add_to_output(&mut self, s: &str) {
-        // this might have to be where we do all the work
-        // if s.starts_with("-") && self.term_writer.is_curline_whitespace_only() {
-        //     println!("found this: {}", s);
-        // }
+        // a continued line doesn't end in a space
+        if self.continued_line {
+            if s == SPACE {
+                return;
+            }
+            self.continued_line = false;
+        }
+
+        if let Some(ref mut capture) = self.tag_capture {
+            capture.push_str(s);
+        }
 
         if self.section_matches() {
             self.term_writer.add_to_buf(s);
         }
     }
 
+    /// Add a space the line won't be broken at, as with \~
+    fn add_unbreakable_space(&mut self) {
+        if let Some(ref mut capture) = self.tag_capture {
+            capture.push_str(SPACE);
+        }
+
+        if self.section_matches() {
+            self.term_writer.add_unbreakable_space();
+        }
+    }
+
     fn add_to_before_output(&mut self, s: &str) {
         if self.section_matches() {
             self.before_section_text.push_str(s);
         }
     }
 }
+
+/// Tokens for a line naming the value given to an option:
+/// .I [name]
+/// = [value]
+/// .br
+fn value_tokens(name: &str, value: &str) -> Vec<Token<TroffToken>> {
+    vec![
+        Token::new(TroffToken::Macro, ".I".into(), true),
+        Token::new(TroffToken::TextWord, name.into(), false),
+        Token::new(TroffToken::TextWord, format!("= {}", value), true),
+        Token::new(TroffToken::Macro, ".br".into(), true),
+    ]
+}
+
+/// Render a standalone run of tokens, such as a single tagged paragraph,
+/// at the default margin.
+#[cfg(test)]
+pub fn render_tokens(tokens: &[Token<TroffToken>]) -> String {
+    render_tokens_with(tokens, ItalicStyle::default())
+}
+
+/// Like render_tokens(), showing italic text as the given style.
+fn render_tokens_with(tokens: &[Token<TroffToken>], italics: ItalicStyle) -> String {
+    let mut parser = TroffParser::new();
+    parser.term_writer.set_italic_style(italics);
+    parser.term_writer.default_margin();
+    parser.parse(tokens.iter());
+
+    parser.result_text().to_owned()
+}
+
+/// The arg of an escape like \C'em', without its delimiters.
+fn strip_delimiters(arg: &str) -> &str {
+    let delimiter = match arg.chars().next() {
+        Some(delimiter) => delimiter,
+        None => return arg,
+    };
+
+    let inner = &arg[delimiter.len_utf8()..];
+    inner.strip_suffix(delimiter).unwrap_or(inner)
+}
+
+/// Render a standalone run of tokens flush-left, without terminal styling.
+/// Returns the text, and the runs of it that are styled.
+pub fn render_tokens_plain(tokens: &[Token<TroffToken>]) -> (String, Vec<StyleSpan>) {
+    let mut parser = TroffParser::new();
+    parser.enable_plain_output();
+    parser.parse(tokens.iter());
+
+    (
+        parser.result_text().to_owned(),
+        parser.style_spans().to_vec(),
+    )
+}
+
+/// Render a page for a test to compare against: without terminal styling,
+/// and without the blank space around it or at the end of its lines.
+#[cfg(test)]
+pub(crate) fn render(page: &str) -> String {
+    use man_parse::troff_token_generator::tokenize_page;
+
+    let (text, _) = render_tokens_plain(&tokenize_page(page));
+
+    let lines: Vec<&str> = text.trim().lines().map(|line| line.trim_end()).collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use man_parse::troff_token_generator::tokenize_page;
+    use text_format::text_format::display_width;
+
+    /// The styled runs of a rendered page, with the text each covers.
+    fn styled(page: &str) -> Vec<(String, FontStyle)> {
+        let (text, spans) = render_tokens_plain(&tokenize_page(page));
+
+        spans
+            .iter()
+            .map(|span| (text[span.start..span.end].to_owned(), span.style))
+            .collect()
+    }
+
+    #[test]
+    fn test_tp_without_tag() {
+        let page = ".TH FOO 1\n.SH OPTIONS\n.TP\n.TP\n\\fB\\-x\\fR\nextract\n";
+        let tokens = tokenize_page(page);
+
+        let mut parser = TroffParser::new();
+        parser.parse(tokens.iter());
+
+        let entries = parser.option_entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].flags, vec!["-x"]);
+    }
+
+    #[test]
+    fn test_docbook_tagged_paragraphs() {
+        let page = ".SH OPTIONS\n.PP\n\\-\\-amend\n.RS 4\nReplace the tip of the current branch\\&.\n.RE\n.PP\n\\-\\-no\\-edit\n.RS 4\nUse the selected commit message\\&.\n.RE\n.PP\nA plain paragraph\\&.\n";
+        let tokens = tokenize_page(page);
+
+        let mut parser = TroffParser::new();
+        parser.parse(tokens.iter());
+
+        let flags: Vec<&Vec<String>> = parser
+            .option_entries()
+            .iter()
+            .map(|entry| &entry.flags)
+            .collect();
+        assert_eq!(flags, vec![&vec!["--amend"], &vec!["--no-edit"]]);
+
+        let mut parser = TroffParser::new().with_args(vec!["--amend".into()]);
+        parser.parse(tokens.iter());
+
+        let explanation = parser.result_text();
+        assert!(explanation.contains("--amend"));
+        assert!(explanation.contains("Replace the tip of the current branch."));
+        assert!(!explanation.contains("selected commit"));
+    }
+
+    #[test]
+    fn test_indents_with_scales_and_bad_values() {
+        assert_eq!(render(".TP 4n\n-x\nextract\n"), "-x\n    extract");
+        assert_eq!(render(".TP 1i\n-x\nextract\n"), "-x\n          extract");
+        assert_eq!(render(".TP wide\n-x\nextract\n"), "-x\n       extract");
+        assert_eq!(render(".RS -4\nindented\n.RE\n"), "indented");
+    }
+
+    #[test]
+    fn test_heading_words_as_separate_args() {
+        let tokens = tokenize_page(".SH SEE ALSO\nls(1)\n.SS Other \"related pages\"\ncat(1)\n");
+
+        let mut parser = TroffParser::new();
+        parser.parse(tokens.iter());
+
+        let titles: Vec<&str> = parser
+            .headings()
+            .iter()
+            .map(|heading| heading.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["SEE ALSO", "Other related pages"]);
+    }
+
+    #[test]
+    fn test_literal_escapes() {
+        assert_eq!(
+            render("grep \\e. a\\\\b \\.x \\'e\\`\n"),
+            "grep \\. a\\b .x ´e`"
+        );
+        assert_eq!(
+            render("\\&.hidden \\%hyph\\:en\\|a\\^te\n"),
+            ".hidden hyphenate"
+        );
+    }
+
+    #[test]
+    fn test_spaces() {
+        assert_eq!(render("a\\ b\\~c\\0d\n"), "a b c d");
+        assert_eq!(render("ab\\tc\n"), "ab   c");
+        assert_eq!(render("ab\\ac\n"), "ab...c");
+    }
+
+    #[test]
+    fn test_escapes_in_quoted_args() {
+        assert_eq!(render(".BR \"grep\\ \\-E\" , \"a\\e\"\n"), "grep -E,a\\");
+    }
+
+    #[test]
+    fn test_previous_font() {
+        assert_eq!(
+            styled("\\fB\\-\\-foo=\\fIvalue\\fP\\fR plain\n"),
+            vec![
+                ("--foo=".to_owned(), FontStyle::Bold),
+                ("value".to_owned(), FontStyle::Italic),
+            ]
+        );
+        assert_eq!(
+            styled("\\fBbold\\fP plain \\f[I]italic\\f[] plain\n"),
+            vec![
+                ("bold".to_owned(), FontStyle::Bold),
+                ("italic".to_owned(), FontStyle::Italic),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_font_names_and_positions() {
+        assert_eq!(
+            styled("\\f(BIboth\\f[CR]code\\f3bold\\f1\n.ft 2\nitalic\n.ft\nplain\n.ft P\nagain\n"),
+            vec![
+                ("both".to_owned(), FontStyle::BoldItalic),
+                ("bold".to_owned(), FontStyle::Bold),
+                ("italic".to_owned(), FontStyle::Italic),
+                ("again".to_owned(), FontStyle::Italic),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_styles_change_only_between_words() {
+        let rendered = render_tokens(&tokenize_page(
+            ".B two bold\n\\fIand\\fR \\f(BIboth\\fR plain\n",
+        ));
+
+        assert_eq!(
+            rendered.trim(),
+            "\x1b[1mtwo bold\x1b[0m \x1b[4mand\x1b[0m \x1b[1;4mboth\x1b[0m plain"
+        );
+    }
+
+    #[test]
+    fn test_wrapping_by_display_width() {
+        // each word is 6 columns wide, but 9 bytes long
+        let page = format!("{}\n", vec!["日本語"; 40].join(" "));
+        let (text, _) = render_tokens_plain(&tokenize_page(&page));
+
+        let max = TroffTermWriter::new().max_line_length();
+        let widths: Vec<usize> = text.lines().map(display_width).collect();
+
+        assert!(widths.len() > 1);
+        assert!(widths.iter().all(|&width| width <= max));
+        assert!(widths[0] > max - 7);
+    }
+
+    #[test]
+    fn test_continued_lines() {
+        assert_eq!(render("one\\c\ntwo\nthree\\\nfour\n"), "onetwo threefour");
+    }
+}
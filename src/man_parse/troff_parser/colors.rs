@@ -0,0 +1,201 @@
+//! Colors, set with '\m[name]' for the text and '\M[name]' for its background,
+//! where '\m[]' and '\M[]' return to the color before.
+//! Besides troff's predefined colors, pages may define their own with '.defcolor'.
+//! Defined colors are shown as closely as the terminal's color depth allows.
+
+use man_parse::troff_parser::macros::strip_comment;
+use man_parse::troff_parser::TroffParser;
+use man_parse::troff_token_generator::TroffToken;
+use simple_parser::token::Token;
+use text_format::text_format::Color;
+
+impl<'a, I> TroffParser<'a, I>
+where
+    I: Iterator<Item = &'a Token<TroffToken>>,
+{
+    /// Request: .defcolor name scheme component...
+    /// Define a color, where the scheme is one of rgb, cmy, cmyk or gray,
+    /// and each component is a number from 0 to 1, or all are given at once in hex, like '#ff8000'.
+    pub(super) fn parse_defcolor(&mut self) {
+        self.consume();
+
+        let line = strip_comment(&self.parse_request_line()).to_owned();
+        let mut words = line.split_whitespace();
+
+        let (name, scheme) = match (words.next(), words.next()) {
+            (Some(name), Some(scheme)) => (name, scheme),
+            _ => return,
+        };
+
+        let components: Vec<&str> = words.collect();
+
+        if let Some((r, g, b)) = color_components(scheme, &components) {
+            let color = self.color_depth.closest(r, g, b);
+            self.colors.insert(name.to_owned(), color);
+        }
+    }
+
+    /// Escape: \mx, \m(xx or \m[name], and the same for \M
+    /// Change the color of the text (\m) or its background (\M).
+    /// Without a name, change back to the color before.
+    pub(super) fn parse_color_format(&mut self) {
+        let background = self.current_token().is_some_and(|tok| tok.value == "M");
+        self.consume();
+
+        let name = self.parse_escape_name();
+
+        let named = self.color(&name);
+        let (current, previous) = if background {
+            (self.term_writer.background(), &mut self.background_colors)
+        } else {
+            (self.term_writer.foreground(), &mut self.foreground_colors)
+        };
+
+        let color = if name.is_empty() {
+            previous.pop().unwrap_or(None)
+        } else {
+            match named {
+                Some(color) => {
+                    previous.push(current);
+                    color
+                }
+                // as in troff, an undefined color changes nothing
+                None => return,
+            }
+        };
+
+        if background {
+            self.term_writer.set_background(color);
+        } else {
+            self.term_writer.set_foreground(color);
+        }
+    }
+
+    /// The color with the given name, if it's defined,
+    /// where the color 'default' is the terminal's own (None).
+    pub(super) fn color(&self, name: &str) -> Option<Option<Color>> {
+        if name == "default" {
+            return Some(None);
+        }
+
+        if let Some(&color) = self.colors.get(name) {
+            return Some(Some(color));
+        }
+
+        predefined_color(name).map(Some)
+    }
+}
+
+/// The colors troff predefines, which are the terminal's eight standard colors.
+fn predefined_color(name: &str) -> Option<Color> {
+    let index = match name {
+        "black" => 0,
+        "red" => 1,
+        "green" => 2,
+        "yellow" => 3,
+        "blue" => 4,
+        "magenta" => 5,
+        "cyan" => 6,
+        "white" => 7,
+        _ => return None,
+    };
+
+    Some(Color::Standard(index))
+}
+
+/// The red, green and blue of a color given to .defcolor, in the given scheme.
+fn color_components(scheme: &str, components: &[&str]) -> Option<(u8, u8, u8)> {
+    let values = component_values(components)?;
+
+    let (r, g, b) = match (scheme, values.as_slice()) {
+        ("rgb", &[r, g, b]) => (r, g, b),
+        ("cmy", &[c, m, y]) => (1.0 - c, 1.0 - m, 1.0 - y),
+        ("cmyk", &[c, m, y, k]) => (
+            (1.0 - c) * (1.0 - k),
+            (1.0 - m) * (1.0 - k),
+            (1.0 - y) * (1.0 - k),
+        ),
+        ("gray", &[gray]) | ("grey", &[gray]) => (gray, gray, gray),
+        _ => return None,
+    };
+
+    let to_byte = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    Some((to_byte(r), to_byte(g), to_byte(b)))
+}
+
+/// The components of a color, each from 0 to 1.
+/// They're either given separately as numbers, like '0.5' (or '0.5f'),
+/// or all together in hex, with two digits each like '#ff8000', or four each like '##ffff80000000'.
+fn component_values(components: &[&str]) -> Option<Vec<f64>> {
+    let first = components.first()?;
+
+    if let Some(hex) = first.strip_prefix("##") {
+        return hex_values(hex, 4);
+    }
+
+    if let Some(hex) = first.strip_prefix('#') {
+        return hex_values(hex, 2);
+    }
+
+    components
+        .iter()
+        .map(|component| component.trim_end_matches('f').parse().ok())
+        .collect()
+}
+
+/// Split hex digits into values of the given number of digits each, scaled from 0 to 1.
+fn hex_values(hex: &str, digits: usize) -> Option<Vec<f64>> {
+    if hex.is_empty() || !hex.len().is_multiple_of(digits) || !hex.is_ascii() {
+        return None;
+    }
+
+    let max = (16_u32.pow(digits as u32) - 1) as f64;
+
+    (0..hex.len())
+        .step_by(digits)
+        .map(|i| {
+            u32::from_str_radix(&hex[i..i + digits], 16)
+                .ok()
+                .map(|value| value as f64 / max)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use man_parse::troff_parser::render_tokens;
+    use man_parse::troff_token_generator::tokenize_page;
+
+    #[test]
+    fn test_color_schemes() {
+        assert_eq!(
+            color_components("rgb", &["1", "0.5", "0"]),
+            Some((255, 128, 0))
+        );
+        assert_eq!(color_components("rgb", &["#ff8000"]), Some((255, 128, 0)));
+        assert_eq!(
+            color_components("rgb", &["##ffff80000000"]),
+            Some((255, 128, 0))
+        );
+        assert_eq!(color_components("cmy", &["0", "1", "1"]), Some((255, 0, 0)));
+        assert_eq!(
+            color_components("cmyk", &["0", "0", "0", "0.5f"]),
+            Some((128, 128, 128))
+        );
+        assert_eq!(color_components("gray", &["0.5"]), Some((128, 128, 128)));
+        assert_eq!(color_components("rgb", &["1", "0"]), None);
+        assert_eq!(color_components("hsv", &["1", "0", "0"]), None);
+    }
+
+    #[test]
+    fn test_colors_return_to_the_color_before() {
+        let page = "\\m[blue]link \\m[red]red\\m[] blue\\m[] plain \\M[yellow]back\\M[]\n";
+
+        assert_eq!(
+            render_tokens(&tokenize_page(page)).trim(),
+            "\x1b[34mlink \x1b[31mred\x1b[34m blue\x1b[0m plain \x1b[43mback\x1b[0m"
+        );
+    }
+}
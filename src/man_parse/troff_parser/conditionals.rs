@@ -0,0 +1,299 @@
+//! Conditionals, like '.if n .sp' or '.ie \n(.g \{ ... \}' with '.el \{ ... \}'
+//! Conditions are evaluated as nroff would when writing to a terminal,
+//! so 'n' (nroff) is true, and 't' (troff) is false.
+//! The body of a conditional is the rest of its line, or a block within '\{' and '\}',
+//! which may span many lines.
+
+use man_parse::troff_parser::expression::eval_numeric;
+use man_parse::troff_parser::TroffParser;
+use man_parse::troff_token_generator::TroffToken;
+use simple_parser::token::Token;
+
+impl<'a, I> TroffParser<'a, I>
+where
+    I: Iterator<Item = &'a Token<TroffToken>>,
+{
+    /// Request: .if condition body
+    /// Parse the body only if the condition is true.
+    /// Request: .ie condition body
+    /// Like .if, except the following .el parses its body only if the condition is false.
+    pub(super) fn parse_if(&mut self) {
+        let is_ie = self
+            .current_raw_token()
+            .is_some_and(|tok| tok.value == ".ie");
+
+        let recorded = self.current_paragraph.as_ref().map(|p| p.tokens.len());
+
+        self.consume();
+        let line = self.parse_request_line();
+
+        let (condition, body_start) = self.eval_condition(&line);
+        if is_ie {
+            self.else_conditions.push(!condition);
+        }
+
+        self.parse_conditional_body(&line[body_start..], condition, recorded);
+    }
+
+    /// Request: .el body
+    /// Parse the body only if the condition of the matching .ie was false.
+    pub(super) fn parse_else(&mut self) {
+        let recorded = self.current_paragraph.as_ref().map(|p| p.tokens.len());
+
+        self.consume();
+        let line = self.parse_request_line();
+
+        // an .el without an .ie never applies
+        let condition = self.else_conditions.pop().unwrap_or(false);
+
+        self.parse_conditional_body(&line, condition, recorded);
+    }
+
+    /// Read the body of a conditional, beginning with the given text,
+    /// and queue it to be parsed if the condition is true.
+    /// 'recorded' is the length of the current paragraph's tokens before the conditional,
+    /// since the conditional itself isn't part of the paragraph, only its body is.
+    fn parse_conditional_body(&mut self, text: &str, condition: bool, recorded: Option<usize>) {
+        let body = self.read_conditional_body(text);
+
+        if let (Some(len), Some(paragraph)) = (recorded, self.current_paragraph.as_mut()) {
+            paragraph.tokens.truncate(len);
+        }
+
+        if condition {
+            self.queue_lines(&body);
+        }
+    }
+
+    /// Read the body of a conditional, given the text following its condition.
+    /// Without '\{', the body is just that text.
+    /// With '\{', the body continues over as many lines as it takes to reach the matching '\}'.
+    fn read_conditional_body(&mut self, text: &str) -> String {
+        let text = text.trim_start();
+
+        let block = match text.strip_prefix("\\{") {
+            Some(block) => block.trim_start(),
+            None => return text.to_owned(),
+        };
+
+        let mut body = String::new();
+        let mut depth = 1;
+        let mut line = block.to_owned();
+
+        loop {
+            if let Some(end) = find_block_end(&line, &mut depth) {
+                body.push_str(&line[..end]);
+                break;
+            }
+
+            body.push_str(&line);
+            body.push('\n');
+
+            line = match self.read_raw_line() {
+                Some(line) => line,
+                None => break,
+            };
+        }
+
+        // a block usually begins with '\{\', so its first line continues onto the next
+        let body = join_continued_lines(&body);
+        body.strip_prefix('\n').unwrap_or(&body).to_owned()
+    }
+
+    /// Read the rest of the current line as written, including its first token.
+    fn read_raw_line(&mut self) -> Option<String> {
+        let mut line = self.current_raw_token()?.value;
+        self.consume();
+
+        while let Some(tok) = self.current_raw_token() {
+            if tok.starts_line {
+                break;
+            }
+
+            line.push_str(&tok.value);
+            self.consume();
+        }
+
+        Some(line)
+    }
+
+    /// Evaluate the condition at the beginning of a conditional's text.
+    /// Returns whether it's true, and where in the text the body begins.
+    fn eval_condition(&mut self, text: &str) -> (bool, usize) {
+        let mut pos = text.len() - text.trim_start().len();
+
+        let mut negated = false;
+        while text[pos..].starts_with('!') {
+            negated = !negated;
+            pos += 1;
+        }
+
+        let rest = &text[pos..];
+
+        let (value, len) = match rest.chars().next() {
+            None => (false, 0),
+            // nroff, on an odd (the first) page
+            Some('n') | Some('o') => (true, 1),
+            // troff, on an even page, or in vroff
+            Some('t') | Some('e') | Some('v') => (false, 1),
+            Some(c @ 'd') | Some(c @ 'r') | Some(c @ 'c') | Some(c @ 'm') | Some(c @ 'F')
+            | Some(c @ 'S') => {
+                let after = &rest[1..];
+                let name_start = after.len() - after.trim_start().len();
+                let name = after[name_start..].split_whitespace().next().unwrap_or("");
+
+                (self.is_defined(c, name), 1 + name_start + name.len())
+            }
+            Some(delimiter) if is_string_delimiter(delimiter) => self.compare_strings(rest),
+            Some(_) => {
+                let end = condition_end(rest);
+                let expr = self.interpolate_text(&rest[..end]);
+
+                (eval_numeric(&expr, 'u').is_some_and(|value| value > 0), end)
+            }
+        };
+
+        (value != negated, pos + len)
+    }
+
+    /// True if the name is defined, for the conditions 'd name' (a string or macro),
+    /// 'r name' (a register) and 'm name' (a color). Characters, fonts and styles all exist.
+    fn is_defined(&self, kind: char, name: &str) -> bool {
+        match kind {
+            'd' => self.strings.contains_key(name) || self.macros.contains_key(name),
+            'r' => self.registers.contains_key(name) || self.builtin_register(name).is_some(),
+            'm' => self.color(name).is_some(),
+            _ => true,
+        }
+    }
+
+    /// Compare the two strings of a condition like 'str1'str2', once they're interpolated.
+    /// Returns whether they're equal, and the length of the condition.
+    fn compare_strings(&mut self, text: &str) -> (bool, usize) {
+        let delimiter = text.chars().next().unwrap();
+        let width = delimiter.len_utf8();
+
+        let mut parts = text[width..].splitn(3, delimiter);
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(first), Some(second), Some(_)) => {
+                let len = width * 3 + first.len() + second.len();
+
+                let first = self.interpolate_text(first);
+                let second = self.interpolate_text(second);
+
+                (first == second, len)
+            }
+            _ => (false, text.len()),
+        }
+    }
+}
+
+/// True if the char begins a string comparison, rather than a numeric expression.
+fn is_string_delimiter(c: char) -> bool {
+    !c.is_alphanumeric() && !c.is_whitespace() && !"()+-.\\|".contains(c)
+}
+
+/// The end of a numeric condition, which is ended by whitespace, or the '\{' of a block.
+fn condition_end(text: &str) -> usize {
+    let space = text.find(char::is_whitespace).unwrap_or(text.len());
+    let block = text.find("\\{").unwrap_or(text.len());
+
+    space.min(block)
+}
+
+/// Find the '\}' closing the block in the given line, counting the '\{'s within,
+/// where 'depth' is how many blocks are open at the start of the line.
+fn find_block_end(line: &str, depth: &mut usize) -> Option<usize> {
+    let mut chars = line.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+
+        match chars.next() {
+            Some((_, '{')) => *depth += 1,
+            Some((_, '}')) => {
+                *depth -= 1;
+                if *depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Join each line ending with '\' onto the line after it.
+fn join_continued_lines(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('\n') | None => {}
+            Some(escaped) => {
+                result.push(c);
+                result.push(escaped);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use man_parse::troff_parser::render;
+
+    #[test]
+    fn test_device_conditions() {
+        assert_eq!(
+            render(".if n nroff\n.if t troff\n.if !t not troff\n"),
+            "nroff not troff"
+        );
+    }
+
+    #[test]
+    fn test_numeric_conditions() {
+        let page = ".nr x 3\n.if \\nx>2 big\n.if \\nx<2 small\n.if (\\nx=3)&\\n(.g both\n.if 1i=240u units\n";
+
+        assert_eq!(render(page), "big both units");
+    }
+
+    #[test]
+    fn test_string_and_defined_conditions() {
+        let page = ".ds s abc\n.if '\\*s'abc' same\n.if \"a\"b\" different\n.if d s string\n.if !d missing undefined\n.if r .g register\n";
+
+        assert_eq!(render(page), "same string undefined register");
+    }
+
+    #[test]
+    fn test_ie_el() {
+        let page = ".ie t troff\n.el nroff\n.ie n yes\n.el no\n";
+
+        assert_eq!(render(page), "nroff yes");
+    }
+
+    #[test]
+    fn test_blocks_from_pod2man() {
+        let page = ".ie n \\{\\\n.    ds L\" <<\n.    ds R\" >>\n'br\\}\n.el\\{\\\n.    ds L\" ``\n.    ds R\" ''\n'br\\}\nsay \\*(L\"hi\\*(R\"\n";
+
+        assert_eq!(render(page), "say <<hi>>");
+    }
+
+    #[test]
+    fn test_nested_false_block() {
+        let page = ".if t \\{\\\nhidden\n.  if n \\{\\\nalso hidden\n.  \\}\n.\\}\nshown\n";
+
+        assert_eq!(render(page), "shown");
+    }
+}
@@ -0,0 +1,233 @@
+//! Numeric expressions, as given to requests like '.nr x (\n(.l-2n)/2'
+//! Troff evaluates them strictly left to right, without precedence,
+//! unless parenthesized: '1+2*3' is 9.
+//! Values are integers in basic units, the same as nroff writing to a terminal:
+//! a character cell is 24 units wide ('1n' or '1m'), and a line is 40 units tall ('1v').
+
+/// The width of a character cell, in basic units.
+pub const UNITS_PER_CHAR: i32 = 24;
+
+/// The height of a line, in basic units.
+pub const UNITS_PER_LINE: i32 = 40;
+
+const UNITS_PER_INCH: f64 = 240.0;
+
+/// Evaluate a numeric expression, where numbers without a scale indicator
+/// are scaled by 'default_scale', i.e. 'u' for .nr or 'v' for .sp
+/// Returns None for a malformed expression, or a division by zero.
+pub fn eval_numeric(expr: &str, default_scale: char) -> Option<i32> {
+    let mut parser = ExpressionParser {
+        chars: expr.trim().chars().collect(),
+        pos: 0,
+        default_scale,
+    };
+
+    let value = parser.expression()?;
+
+    if parser.pos == parser.chars.len() {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Evaluate an indentation, as given to macros like .TP and .RS, in character cells.
+/// Numbers without a scale indicator are ens, so '4' and '4n' are both four cells.
+/// Returns None for a malformed or negative indentation.
+pub fn eval_indent(expr: &str) -> Option<usize> {
+    let units = eval_numeric(expr, 'n')?;
+
+    if units < 0 {
+        return None;
+    }
+
+    Some(((units + UNITS_PER_CHAR / 2) / UNITS_PER_CHAR) as usize)
+}
+
+/// How many basic units the given scale indicator stands for, if it is one.
+fn units_per(scale: char) -> Option<f64> {
+    let units = match scale {
+        'i' => UNITS_PER_INCH,
+        'c' => UNITS_PER_INCH / 2.54,
+        'p' => UNITS_PER_INCH / 72.0,
+        'P' => UNITS_PER_INCH / 6.0,
+        'm' | 'n' => UNITS_PER_CHAR as f64,
+        'M' => UNITS_PER_CHAR as f64 / 100.0,
+        'v' => UNITS_PER_LINE as f64,
+        'u' => 1.0,
+        _ => return None,
+    };
+
+    Some(units)
+}
+
+struct ExpressionParser {
+    chars: Vec<char>,
+    pos: usize,
+    default_scale: char,
+}
+
+impl ExpressionParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
+    }
+
+    /// expression: term (operator term)*
+    fn expression(&mut self) -> Option<i32> {
+        let mut value = self.term()?;
+
+        while let Some(op) = self.operator() {
+            let rhs = self.term()?;
+
+            value = match op {
+                "+" => value.checked_add(rhs)?,
+                "-" => value.checked_sub(rhs)?,
+                "*" => value.checked_mul(rhs)?,
+                "/" => value.checked_div(rhs)?,
+                "%" => value.checked_rem(rhs)?,
+                "<" => (value < rhs) as i32,
+                ">" => (value > rhs) as i32,
+                "<=" => (value <= rhs) as i32,
+                ">=" => (value >= rhs) as i32,
+                "=" | "==" => (value == rhs) as i32,
+                "<>" => (value != rhs) as i32,
+                "<?" => value.min(rhs),
+                ">?" => value.max(rhs),
+                "&" => (value > 0 && rhs > 0) as i32,
+                ":" => (value > 0 || rhs > 0) as i32,
+                _ => unreachable!(),
+            };
+        }
+
+        Some(value)
+    }
+
+    /// Read a binary operator, if one is next.
+    fn operator(&mut self) -> Option<&'static str> {
+        let two = match (self.peek(), self.peek_at(1)) {
+            (Some('<'), Some('=')) => Some("<="),
+            (Some('>'), Some('=')) => Some(">="),
+            (Some('='), Some('=')) => Some("=="),
+            (Some('<'), Some('>')) => Some("<>"),
+            (Some('<'), Some('?')) => Some("<?"),
+            (Some('>'), Some('?')) => Some(">?"),
+            _ => None,
+        };
+
+        if let Some(op) = two {
+            self.pos += 2;
+            return Some(op);
+        }
+
+        let one = match self.peek()? {
+            '+' => "+",
+            '-' => "-",
+            '*' => "*",
+            '/' => "/",
+            '%' => "%",
+            '<' => "<",
+            '>' => ">",
+            '=' => "=",
+            '&' => "&",
+            ':' => ":",
+            _ => return None,
+        };
+
+        self.pos += 1;
+        Some(one)
+    }
+
+    /// term: ['+' | '-'] (number | '(' expression ')')
+    fn term(&mut self) -> Option<i32> {
+        match self.peek()? {
+            '-' => {
+                self.pos += 1;
+                self.term()?.checked_neg()
+            }
+            '+' => {
+                self.pos += 1;
+                self.term()
+            }
+            '(' => {
+                self.pos += 1;
+                let value = self.expression()?;
+
+                if self.peek() != Some(')') {
+                    return None;
+                }
+                self.pos += 1;
+
+                Some(value)
+            }
+            _ => self.number(),
+        }
+    }
+
+    /// number: digits ['.' digits] [scale indicator]
+    fn number(&mut self) -> Option<i32> {
+        let start = self.pos;
+
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        let number: f64 = digits.parse().ok()?;
+
+        let scale = match self.peek().and_then(units_per) {
+            Some(units) => {
+                self.pos += 1;
+                units
+            }
+            None => units_per(self.default_scale)?,
+        };
+
+        Some((number * scale).round() as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_left_to_right() {
+        assert_eq!(eval_numeric("1+2*3", 'u'), Some(9));
+        assert_eq!(eval_numeric("1+(2*3)", 'u'), Some(7));
+        assert_eq!(eval_numeric("-4/2", 'u'), Some(-2));
+        assert_eq!(eval_numeric("1/0", 'u'), None);
+        assert_eq!(eval_numeric("1+", 'u'), None);
+        assert_eq!(eval_numeric("-(-2147483647-1)", 'u'), None);
+    }
+
+    #[test]
+    fn test_scales() {
+        assert_eq!(eval_numeric("2n", 'u'), Some(48));
+        assert_eq!(eval_numeric("1i", 'u'), Some(240));
+        assert_eq!(eval_numeric("1.5", 'v'), Some(60));
+        assert_eq!(eval_numeric("3", 'm'), Some(72));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(eval_numeric("2>1", 'u'), Some(1));
+        assert_eq!(eval_numeric("2<=1", 'u'), Some(0));
+        assert_eq!(eval_numeric("1=1&0", 'u'), Some(0));
+        assert_eq!(eval_numeric("0:1", 'u'), Some(1));
+        assert_eq!(eval_numeric("3<?5", 'u'), Some(3));
+    }
+
+    #[test]
+    fn test_indents() {
+        assert_eq!(eval_indent("4"), Some(4));
+        assert_eq!(eval_indent("4n"), Some(4));
+        assert_eq!(eval_indent("0.5i"), Some(5));
+        assert_eq!(eval_indent("2+3"), Some(5));
+        assert_eq!(eval_indent("-4"), None);
+        assert_eq!(eval_indent("wide"), None);
+    }
+}
@@ -0,0 +1,289 @@
+//! Strings, and their interpolation into the token stream.
+//! A string is defined with '.ds name value', and interpolated wherever
+//! '\*x', '\*(xx' or '\*[name]' appears, as if its value had been written there instead.
+//! Registers are interpolated the same way, with '\nx', see registers.rs
+
+use man_parse::troff_parser::TroffParser;
+use man_parse::troff_token_generator::{tokenize_page, TroffToken, TroffTokenGenerator};
+use simple_parser::split_whitespace::WhitespaceSplitInclusive;
+use simple_parser::token::Token;
+use simple_parser::token_generator::TokenGenerator;
+use std::collections::HashMap;
+
+/// The most interpolations done while parsing a single page.
+/// Guards against strings that interpolate themselves, i.e. '.ds x \*x'
+pub const MAX_INTERPOLATIONS: usize = 100_000;
+
+/// The strings the man macros define for every page.
+pub fn predefined_strings() -> HashMap<String, String> {
+    let mut strings = HashMap::new();
+
+    strings.insert("R".to_owned(), "\\(rg".to_owned());
+    strings.insert("Tm".to_owned(), "\\(tm".to_owned());
+    strings.insert("lq".to_owned(), "\\(lq".to_owned());
+    strings.insert("rq".to_owned(), "\\(rq".to_owned());
+    strings.insert("S".to_owned(), String::new());
+
+    strings
+}
+
+/// Tokenize text that is part of a line, rather than the start of one,
+/// such as the value of a string being interpolated.
+pub fn tokenize_inline(text: &str) -> Vec<Token<TroffToken>> {
+    let generator = TroffTokenGenerator {};
+
+    text.split_whitespace_inclusive()
+        .flat_map(|word| generator.generate(word, false))
+        .collect()
+}
+
+impl<'a, I> TroffParser<'a, I>
+where
+    I: Iterator<Item = &'a Token<TroffToken>>,
+{
+    /// Expand the interpolation the current token begins, if it begins one,
+    /// so that the current token is the first of the interpolated tokens.
+    /// Interpolated tokens may begin interpolations of their own, which are expanded too.
+    pub(super) fn expand_current_token(&mut self) {
+        while let Some(tok) = self.current_token.take() {
+            if self.try_interpolate(&tok) {
+                self.current_token = self.next_raw_token();
+            } else {
+                self.current_token = Some(tok);
+                break;
+            }
+        }
+
+        self.current_token_expanded = true;
+    }
+
+    /// If the given token (just taken from the input) begins an interpolation,
+    /// like '\*(xx' or '\n(xx', read the rest of it and queue up the interpolated tokens in its place.
+    /// Returns false, having read nothing more, if it doesn't.
+    fn try_interpolate(&mut self, tok: &Token<TroffToken>) -> bool {
+        if tok.class != TroffToken::Backslash || self.interpolations >= MAX_INTERPOLATIONS {
+            return false;
+        }
+
+        let command = match self.next_raw_token() {
+            Some(command) => command,
+            None => return false,
+        };
+
+        if command.class != TroffToken::EscapeCommand || !["*", "n"].contains(&&*command.value) {
+            self.pending_tokens.push_front(command);
+            return false;
+        }
+
+        self.interpolations += 1;
+
+        let value = if command.value == "n" {
+            let sign = self.interpolation_sign();
+            let name = self.interpolation_name();
+
+            self.register_value(&name, sign)
+        } else {
+            let name = self.interpolation_name();
+
+            self.strings.get(&name).cloned().unwrap_or_default()
+        };
+
+        self.queue_interpolated(tokenize_inline(&value), tok.starts_line);
+
+        true
+    }
+
+    /// Read the name following an escape like '\*' or '\n', in any of its three forms:
+    /// 'x', '(xx' or '[name]'.
+    fn interpolation_name(&mut self) -> String {
+        let first = match self.next_raw_token() {
+            Some(first) => first,
+            None => return String::new(),
+        };
+
+        match first.class {
+            TroffToken::ArgOpenParen => self.next_raw_token().map(|t| t.value).unwrap_or_default(),
+            TroffToken::ArgOpenBracket => {
+                let name = self.next_raw_token().map(|t| t.value).unwrap_or_default();
+
+                match self.next_raw_token() {
+                    Some(ref close) if close.class == TroffToken::ArgCloseBracket => {}
+                    Some(other) => self.pending_tokens.push_front(other),
+                    None => {}
+                }
+
+                name
+            }
+            TroffToken::CommandArg => first.value,
+            _ => {
+                self.pending_tokens.push_front(first);
+                String::new()
+            }
+        }
+    }
+
+    /// Queue tokens to be parsed next, in place of the escape that produced them.
+    /// If the escape began a line, whatever comes in its place begins the line instead.
+    pub(super) fn queue_interpolated(
+        &mut self,
+        mut tokens: Vec<Token<TroffToken>>,
+        starts_line: bool,
+    ) {
+        if starts_line {
+            if let Some(first) = tokens.first_mut() {
+                first.starts_line = true;
+            } else if let Some(mut next) = self.next_raw_token() {
+                next.starts_line = true;
+                self.pending_tokens.push_front(next);
+            }
+        }
+
+        for tok in tokens.into_iter().rev() {
+            self.pending_tokens.push_front(tok);
+        }
+    }
+
+    /// Queue whole lines of a page to be parsed next, in place of the line just read,
+    /// such as the body of a macro being called.
+    pub(super) fn queue_lines(&mut self, text: &str) {
+        // the lines come before whatever followed the line just read
+        if let Some(next) = self.current_token.take() {
+            self.pending_tokens.push_front(next);
+        }
+
+        for tok in tokenize_page(text).into_iter().rev() {
+            self.pending_tokens.push_front(tok);
+        }
+
+        self.current_token = self.next_raw_token();
+        self.current_token_expanded = false;
+    }
+
+    /// Request: .ds name [string]
+    /// Define a string, replacing any existing string with that name.
+    /// A leading '"' is stripped from the string, so that it may begin with spaces.
+    /// Request: .as name [string]
+    /// Like .ds, except the string is appended to any existing string with that name.
+    pub(super) fn parse_ds(&mut self) {
+        let append = self
+            .current_raw_token()
+            .is_some_and(|tok| tok.value == ".as");
+        self.consume();
+
+        let name = self.parse_request_name();
+        let value = self.parse_request_string();
+
+        if name.is_empty() {
+            return;
+        }
+
+        if append {
+            self.strings.entry(name).or_default().push_str(&value);
+        } else {
+            self.strings.insert(name, value);
+        }
+    }
+
+    /// Parse the name given to a request, like the 'L"' of '.ds L" ``'.
+    /// Unlike a macro arg, a name ends only at whitespace, so it may contain quotes.
+    pub(super) fn parse_request_name(&mut self) -> String {
+        self.skip_request_spaces();
+
+        let mut name = String::new();
+
+        while let Some(tok) = self.current_raw_token() {
+            if tok.starts_line || tok.class == TroffToken::Whitespace {
+                break;
+            }
+
+            name.push_str(&tok.value);
+            self.consume();
+        }
+
+        name
+    }
+
+    /// Parse the rest of a request's line as a string argument, as written.
+    /// A leading '"' is stripped, and as in troff's copy mode, '\\' is reduced to '\'.
+    /// Unlike troff, interpolations in the string are left to be expanded where it's used.
+    pub(super) fn parse_request_string(&mut self) -> String {
+        let line = self.parse_request_line();
+        let value = line.strip_prefix('"').unwrap_or(&line);
+
+        value.replace("\\\\", "\\")
+    }
+
+    /// Parse the rest of a request's line exactly as written,
+    /// leaving the current token as the first of the next line.
+    pub(super) fn parse_request_line(&mut self) -> String {
+        self.skip_request_spaces();
+
+        let mut line = String::new();
+
+        while let Some(tok) = self.current_raw_token() {
+            if tok.starts_line {
+                break;
+            }
+
+            line.push_str(&tok.value);
+            self.consume();
+        }
+
+        line
+    }
+
+    /// Skip the whitespace between a request's args, without leaving its line.
+    fn skip_request_spaces(&mut self) {
+        while let Some(tok) = self.current_raw_token() {
+            if tok.starts_line || tok.class != TroffToken::Whitespace {
+                break;
+            }
+
+            self.consume();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use man_parse::troff_parser::render;
+
+    #[test]
+    fn test_string_forms() {
+        let page = ".ds x ex\n.ds (y why\n.ds long name\n\\*x \\*((y \\*[long]\n";
+
+        assert_eq!(render(page), "ex why name");
+    }
+
+    #[test]
+    fn test_quotes_from_pod2man() {
+        let page = ".    ds L\" ``\n.    ds R\" ''\nsee \\*(L\"perlvar\\*(R\" for details\n";
+
+        assert_eq!(render(page), "see ``perlvar'' for details");
+    }
+
+    #[test]
+    fn test_append_and_leading_quote() {
+        let page = ".ds greeting hello\n.as greeting \" \\fBworld\\fR\n>\\*[greeting]<\n";
+
+        assert_eq!(render(page), ">hello world<");
+    }
+
+    #[test]
+    fn test_undefined_and_recursive_strings() {
+        assert_eq!(render("x\\*[missing]y\n"), "xy");
+
+        // '\\' delays the interpolation until the string is used
+        assert!(render(".ds self a\\\\*[self]\n\\*[self]\n").starts_with("aaa"));
+    }
+
+    #[test]
+    fn test_defined_on_previous_line() {
+        assert_eq!(render(".ds s ess\n\\*s\n"), "ess");
+    }
+
+    #[test]
+    fn test_predefined_strings() {
+        assert_eq!(render("Linux\\*R\n"), "Linux®");
+    }
+}
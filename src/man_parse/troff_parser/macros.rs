@@ -0,0 +1,315 @@
+//! Macros defined by the page itself, with '.de name' ... '..'
+//! Calling a macro, like '.Vb 4', parses its body in place of the call,
+//! with the args of the call substituted for '\$1', '\$2', ...
+
+use man_parse::troff_parser::interpolation::MAX_INTERPOLATIONS;
+use man_parse::troff_parser::TroffParser;
+use man_parse::troff_token_generator::TroffToken;
+use simple_parser::token::Token;
+
+impl<'a, I> TroffParser<'a, I>
+where
+    I: Iterator<Item = &'a Token<TroffToken>>,
+{
+    /// Request: .de name [end]
+    /// Define a macro, whose body is every following line
+    /// until a line calling 'end' (by default, the line '..').
+    /// Request: .am name [end]
+    /// Like .de, except the body is appended to any existing macro with that name.
+    /// The body is read in copy mode, so '\\' is reduced to '\'
+    /// and '\\$1' in the body becomes '\$1'.
+    pub(super) fn parse_de(&mut self) {
+        let append = self
+            .current_raw_token()
+            .is_some_and(|tok| tok.value == ".am");
+        self.consume();
+
+        let line = strip_comment(&self.parse_request_line()).to_owned();
+        let mut words = line.split_whitespace();
+
+        let name = words.next().unwrap_or("").to_owned();
+        let end = format!(".{}", words.next().unwrap_or("."));
+
+        let body = self.read_macro_body(&end);
+
+        if name.is_empty() {
+            return;
+        }
+
+        if append {
+            self.macros.entry(name).or_default().push_str(&body);
+        } else {
+            self.macros.insert(name, body);
+        }
+    }
+
+    /// Read the lines of a macro body as written, up to and including the line calling 'end'.
+    fn read_macro_body(&mut self, end: &str) -> String {
+        let mut body = String::new();
+
+        while let Some(tok) = self.current_raw_token() {
+            if tok.starts_line {
+                if tok.class == TroffToken::Macro && tok.value == end {
+                    self.consume();
+                    self.consume_line();
+                    break;
+                }
+
+                if !body.is_empty() {
+                    body.push('\n');
+                }
+            }
+
+            body.push_str(&tok.value);
+            self.consume();
+        }
+
+        if !body.is_empty() {
+            body.push('\n');
+        }
+
+        body.replace("\\\\", "\\")
+    }
+
+    /// Request: .rm name
+    /// Remove the macro or string with the given name.
+    pub(super) fn parse_rm(&mut self) {
+        self.consume();
+
+        let line = strip_comment(&self.parse_request_line()).to_owned();
+
+        for name in line.split_whitespace() {
+            self.macros.remove(name);
+            self.strings.remove(name);
+        }
+    }
+
+    /// Request: .rn old new
+    /// Rename the macro or string 'old' to 'new'.
+    pub(super) fn parse_rn(&mut self) {
+        self.consume();
+
+        let line = strip_comment(&self.parse_request_line()).to_owned();
+        let mut words = line.split_whitespace();
+
+        if let (Some(old), Some(new)) = (words.next(), words.next()) {
+            if let Some(body) = self.macros.remove(old) {
+                self.macros.insert(new.to_owned(), body);
+            } else if let Some(value) = self.strings.remove(old) {
+                self.strings.insert(new.to_owned(), value);
+            }
+        }
+    }
+
+    /// Expand a call of a macro defined by the page.
+    /// The current token is expected to be the call, i.e. '.Vb'.
+    /// The body is parsed in place of the call's line, with the args substituted in.
+    pub(super) fn expand_macro(&mut self) {
+        let name = self.current_raw_token().unwrap().value[1..].to_owned();
+
+        // the call itself isn't part of a paragraph; its expansion is
+        let recorded = self.current_paragraph.as_ref().map(|p| p.tokens.len());
+
+        self.consume();
+        // args are read in copy mode, the same as the body was
+        let line = self.parse_request_line().replace("\\\\", "\\");
+        let args = split_macro_args(&line);
+
+        if let (Some(len), Some(paragraph)) = (recorded, self.current_paragraph.as_mut()) {
+            paragraph.tokens.truncate(len);
+        }
+
+        if self.interpolations >= MAX_INTERPOLATIONS {
+            return;
+        }
+        self.interpolations += 1;
+
+        let body = substitute_args(&self.macros[&name], &name, &args);
+        self.queue_lines(&body);
+    }
+}
+
+/// Remove a trailing comment, beginning with '\"', from a line.
+pub fn strip_comment(line: &str) -> &str {
+    let mut chars = line.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some((_, '"')) => return line[..i].trim_end(),
+                _ => continue,
+            }
+        }
+    }
+
+    line
+}
+
+/// Split the args of a macro call the way troff does.
+/// Args are separated by spaces, unless quoted: "one arg".
+/// Within a quoted arg, "" is a literal quote.
+fn split_macro_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = strip_comment(line).chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let quoted = match chars.peek() {
+            None => break,
+            Some(&'"') => {
+                chars.next();
+                true
+            }
+            Some(_) => false,
+        };
+
+        let mut arg = String::new();
+
+        while let Some(c) = chars.next() {
+            if quoted && c == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    arg.push('"');
+                    continue;
+                }
+                break;
+            }
+
+            if !quoted && c.is_whitespace() {
+                break;
+            }
+
+            arg.push(c);
+
+            // an escaped char never ends the arg
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    arg.push(escaped);
+                }
+            }
+        }
+
+        args.push(arg);
+    }
+
+    args
+}
+
+/// Substitute the args of a macro call into its body:
+/// '\$0' is the macro's name, '\$1' to '\$9' (or '\$(nn', '\$[nn]') are single args,
+/// '\$*' is every arg joined by spaces, '\$@' is every arg, each quoted,
+/// and '\n(.$' is how many args there are.
+fn substitute_args(body: &str, name: &str, args: &[String]) -> String {
+    let arg = |n: usize| -> &str {
+        match n {
+            0 => name,
+            n => args.get(n - 1).map(|arg| arg.as_str()).unwrap_or(""),
+        }
+    };
+
+    let count = args.len().to_string();
+
+    let mut result = String::with_capacity(body.len());
+    let mut rest = body;
+
+    while let Some(i) = rest.find('\\') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let (replacement, len) = if rest.starts_with("\\$*") {
+            (args.join(" "), 3)
+        } else if rest.starts_with("\\$@") {
+            let quoted: Vec<String> = args.iter().map(|arg| format!("\"{}\"", arg)).collect();
+            (quoted.join(" "), 3)
+        } else if rest.starts_with("\\n(.$") {
+            (count.clone(), 5)
+        } else if rest.starts_with("\\n[.$]") {
+            (count.clone(), 6)
+        } else if rest.starts_with("\\$(") && rest.len() >= 5 {
+            match rest[3..5].parse() {
+                Ok(n) => (arg(n).to_owned(), 5),
+                Err(_) => (rest[..2].to_owned(), 2),
+            }
+        } else if rest.starts_with("\\$[") {
+            match rest.find(']').map(|end| (rest[3..end].parse(), end)) {
+                Some((Ok(n), end)) => (arg(n).to_owned(), end + 1),
+                _ => (rest[..2].to_owned(), 2),
+            }
+        } else if let Some(after) = rest.strip_prefix("\\$") {
+            match after.chars().next().and_then(|c| c.to_digit(10)) {
+                Some(n) => (arg(n as usize).to_owned(), 3),
+                None => (rest[..2].to_owned(), 2),
+            }
+        } else {
+            // any other escape is left as-is, including an escaped backslash
+            let len = 1 + rest[1..].chars().next().map_or(0, |c| c.len_utf8());
+            (rest[..len].to_owned(), len)
+        };
+
+        result.push_str(&replacement);
+        rest = &rest[len..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use man_parse::troff_parser::render;
+
+    #[test]
+    fn test_split_macro_args() {
+        assert_eq!(
+            split_macro_args(r#"Item "say ""hi""" \-x \" comment"#),
+            vec!["Item", "say \"hi\"", "\\-x"]
+        );
+    }
+
+    #[test]
+    fn test_substitute_args() {
+        let args = vec!["a".to_owned(), "b c".to_owned()];
+
+        assert_eq!(
+            substitute_args("\\$0:\\$1:\\$2:\\$3:\\n(.$", "XX", &args),
+            "XX:a:b c::2"
+        );
+        assert_eq!(
+            substitute_args("\\$*|\\$@", "XX", &args),
+            "a b c|\"a\" \"b c\""
+        );
+        assert_eq!(substitute_args("\\fB\\$1\\fR", "XX", &args), "\\fBa\\fR");
+    }
+
+    #[test]
+    fn test_define_and_call() {
+        let page = ".de Gr \\\" greet\nHello, \\\\$1 and \\\\$2!\n..\n.Gr world \"every one\"\n";
+
+        assert_eq!(render(page), "Hello, world and every one!");
+    }
+
+    #[test]
+    fn test_verbatim_from_pod2man() {
+        let page = ".de Vb \\\" Begin verbatim text\n.ft CW\n.nf\n.ne \\\\$1\n..\n.de Ve \\\" End verbatim text\n.ft R\n.fi\n..\nExample:\n.Vb 2\n\\&  my $x = 1;\n\\&  print $x;\n.Ve\nDone.\n";
+
+        assert_eq!(render(page), "Example:\n  my $x = 1;\n  print $x;\nDone.");
+    }
+
+    #[test]
+    fn test_append_remove_and_rename() {
+        let page = ".de A\none\n..\n.am A\ntwo\n..\n.rn A B\n.A\n.B\n.rm B\n.B\n";
+
+        assert_eq!(render(page), "one two");
+    }
+
+    #[test]
+    fn test_custom_end_macro() {
+        let page = ".de X En\nbody\n.En\n.X\n";
+
+        assert_eq!(render(page), "body");
+    }
+}
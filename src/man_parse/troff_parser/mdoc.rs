@@ -0,0 +1,872 @@
+//! Parsing for mdoc, the semantic macro package of BSD man pages.
+//! See: https://man.openbsd.org/mdoc.7
+//!
+//! Unlike man macros, most mdoc macros can be called from the line of another macro,
+//! i.e. '.Op Fl s Ar bytes' produces '[-s bytes]', with '-s' in bold and 'bytes' in italics.
+//! A word preceded by '\&' is never called, so '.Op \&Fl s' produces '[Fl s]'.
+//!
+//! Lists (.Bl) and displays (.Bd) nest, each pushing a margin in the TroffTermWriter
+//! for as long as it is open.
+
+use man_parse::man_section::ManSection;
+use man_parse::special_chars::special_character;
+use man_parse::term_writer::font_style::FontStyle;
+use man_parse::troff_parser::{TroffParser, SPACE};
+use man_parse::troff_token_generator::TroffToken;
+use simple_parser::token::Token;
+use text_format::text_format::display_width;
+
+/// Macros that may be called from within the line of another macro,
+/// i.e. the 'Fl' and 'Ar' in '.Op Fl s Ar bytes'
+const CALLABLE_MACROS: &[&str] = &[
+    "Aq", "Ar", "Bq", "Brq", "Cm", "Dq", "Dv", "Em", "Er", "Ev", "Fl", "Ic", "Li", "Nm", "No",
+    "Ns", "Op", "Pa", "Pq", "Ql", "Qq", "Sq", "Sy", "Va", "Xr",
+];
+
+/// Punctuation that attaches to the word before it, i.e. the ',' in '.Ar file ,'
+const CLOSING_DELIMITERS: &[&str] = &[".", ",", ":", ";", ")", "]", "?", "!"];
+
+/// Punctuation that attaches to the word after it
+const OPENING_DELIMITERS: &[&str] = &["(", "["];
+
+/// True if the macro (without its leading '.') may be called from another macro's line.
+pub fn is_callable(name: &str) -> bool {
+    CALLABLE_MACROS.contains(&name)
+}
+
+/// The text surrounding the rest of the line for an enclosing macro,
+/// i.e. '.Op Fl a' is written '[-a]'
+/// Quotes are special characters, written in ASCII if 'ascii' is set.
+fn enclosure(name: &str, ascii: bool) -> Option<(String, String)> {
+    let marks = |open: &str, close: &str| Some((open.to_owned(), close.to_owned()));
+    let quotes = |open: &str, close: &str| Some((glyph(open, ascii), glyph(close, ascii)));
+
+    match name {
+        "Op" | "Bq" => marks("[", "]"),
+        "Pq" => marks("(", ")"),
+        "Brq" => marks("{", "}"),
+        "Aq" => marks("<", ">"),
+        "Dq" => quotes("lq", "rq"),
+        "Sq" | "Ql" => quotes("oq", "cq"),
+        "Qq" => marks("\"", "\""),
+        _ => None,
+    }
+}
+
+/// The special character with the given name, i.e. "\u{2022}" for 'bu',
+/// or its ASCII fallback if 'ascii' is set.
+fn glyph(name: &str, ascii: bool) -> String {
+    special_character(name, ascii).unwrap_or_default()
+}
+
+/// The space between the columns of a '-column' list
+const COLUMN_GAP: usize = 2;
+
+/// The kinds of list that .Bl begins, as named by its first argument, i.e. '-tag'
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ListKind {
+    Tag,
+    Hang,
+    Ohang,
+    Inset,
+    Bullet,
+    Dash,
+    Enum,
+    Item,
+    Column,
+}
+
+impl ListKind {
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "-tag" => Some(ListKind::Tag),
+            "-hang" => Some(ListKind::Hang),
+            "-ohang" => Some(ListKind::Ohang),
+            "-inset" | "-diag" => Some(ListKind::Inset),
+            "-bullet" => Some(ListKind::Bullet),
+            "-dash" | "-hyphen" => Some(ListKind::Dash),
+            "-enum" => Some(ListKind::Enum),
+            "-item" => Some(ListKind::Item),
+            "-column" => Some(ListKind::Column),
+            _ => None,
+        }
+    }
+
+    /// The indent of an item's body, when the list doesn't give one with '-width'
+    fn default_width(&self) -> usize {
+        match *self {
+            ListKind::Tag | ListKind::Hang => 8,
+            ListKind::Bullet | ListKind::Dash => 2,
+            ListKind::Enum => 4,
+            _ => 0,
+        }
+    }
+
+    /// True if the list's items begin with a tag, which may document an option.
+    fn is_tagged(&self) -> bool {
+        matches!(
+            *self,
+            ListKind::Tag | ListKind::Hang | ListKind::Ohang | ListKind::Inset
+        )
+    }
+}
+
+/// An open mdoc list (.Bl) or display (.Bd)
+pub struct MdocBlock {
+    /// The kind of list, or None for a display.
+    list: Option<ListKind>,
+
+    /// The indent of an item's body.
+    width: usize,
+
+    /// True if there are no blank lines between items.
+    compact: bool,
+
+    /// How many items have begun, for numbering '-enum' lists.
+    items: usize,
+
+    /// The width of each column of a '-column' list.
+    columns: Vec<usize>,
+
+    /// The indent when the block began, restored when it ends.
+    indent: usize,
+
+    /// True if no-fill mode was on when the block began, restored when it ends.
+    nofill: bool,
+}
+
+/// A width given to .Bl or .Bd as a number with an optional unit, like '10n',
+/// or as one of mdoc's named widths.
+/// Returns None if the width is a string, which stands for the width of its text.
+fn scaled_width(arg: &str) -> Option<usize> {
+    match arg {
+        "Ds" | "indent" => return Some(6),
+        "indent-two" => return Some(12),
+        "left" => return Some(0),
+        _ => {}
+    }
+
+    let number_end = arg
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(arg.len());
+
+    let number = arg[..number_end].parse::<f32>().ok()?;
+
+    let scale = match &arg[number_end..] {
+        "" | "n" | "m" | "v" | "u" => 1.0,
+        "i" => 10.0,
+        "c" => 4.0,
+        "P" => 1.5,
+        _ => return None,
+    };
+
+    Some((number * scale) as usize)
+}
+
+/// The style a macro writes its words in, if any.
+fn macro_style(name: &str) -> Option<FontStyle> {
+    match name {
+        "Fl" | "Cm" | "Nm" | "Sy" | "Ic" => Some(FontStyle::Bold),
+        "Ar" | "Pa" | "Em" | "Va" => Some(FontStyle::Italic),
+        _ => None,
+    }
+}
+
+/// The state of a single mdoc macro line, as its words are written.
+struct MdocLine {
+    /// The macro most recently called on the line, which decides how words are written.
+    current: String,
+
+    /// How many words the current macro has written.
+    words: usize,
+
+    /// The closing text of each enclosure opened on the line, innermost last.
+    closers: Vec<String>,
+
+    /// Closing punctuation not yet written.
+    /// At the end of the line it goes after the enclosures are closed,
+    /// so '.Pq Xr ls 1 .' is written '(ls(1)).'
+    delimiters: String,
+
+    /// True if a space should be written before the next word.
+    space: bool,
+
+    /// The column being written, in a '-column' list.
+    column: usize,
+}
+
+impl<'a, I> TroffParser<'a, I>
+where
+    I: Iterator<Item = &'a Token<TroffToken>>,
+{
+    /// .Dd date, .Dt TITLE section, .Os system
+    /// The prologue of an mdoc page, which only informs the page header and footer,
+    /// so it isn't written.
+    pub(super) fn parse_mdoc_prologue(&mut self) {
+        self.consume_class(TroffToken::Macro);
+        self.consume_line();
+    }
+
+    /// .Nd description
+    /// The one line description of the page's subject, following '.Nm name' in the NAME section.
+    pub(super) fn parse_nd(&mut self) {
+        self.consume_val(".Nd");
+        self.consume_spaces();
+
+        self.add_to_output("-");
+        self.add_to_output(SPACE);
+        self.parse_line();
+        self.add_to_output(SPACE);
+    }
+
+    /// A line starting with a callable macro, i.e. '.Op Fl s Ar bytes'
+    pub(super) fn parse_mdoc_line(&mut self) {
+        let name = self.current_token().unwrap().value[1..].to_owned();
+        self.consume_class(TroffToken::Macro);
+
+        // in the synopsis, each use of the command begins a new line
+        if name == "Nm" && self.current_section == Some(ManSection::Synopsis) {
+            self.add_linebreak_single();
+        }
+
+        self.parse_mdoc_words(Some(&name));
+    }
+
+    /// Parse and output the words of an mdoc line, up to the end of the line.
+    /// If the line began with a macro, it is given as 'name'.
+    /// Otherwise, words are written as plain text until a macro is called.
+    fn parse_mdoc_words(&mut self, name: Option<&str>) {
+        let mut line = MdocLine {
+            current: String::new(),
+            words: 0,
+            closers: Vec::new(),
+            delimiters: String::new(),
+            space: false,
+            column: 0,
+        };
+
+        if let Some(name) = name {
+            self.mdoc_call(name, &mut line);
+        }
+
+        while let Some(tok) = self.current_token() {
+            if tok.starts_line {
+                break;
+            }
+
+            let value = tok.value.as_str();
+
+            match tok.class {
+                // in a column list, cells are separated by tabs or 'Ta'
+                TroffToken::Whitespace if value.contains('\t') && self.in_column_list() => {
+                    self.consume_spaces();
+                    self.mdoc_next_column(&mut line);
+                }
+                TroffToken::TextWord if value == "Ta" && self.in_column_list() => {
+                    self.consume();
+                    self.mdoc_next_column(&mut line);
+                }
+
+                // mdoc decides the spacing between words itself
+                TroffToken::Whitespace => self.consume_spaces(),
+                TroffToken::TextWord if is_callable(value) => {
+                    self.consume();
+                    self.mdoc_end_macro(&mut line);
+                    self.mdoc_call(value, &mut line);
+                }
+                TroffToken::TextWord if CLOSING_DELIMITERS.contains(&value) => {
+                    self.consume();
+                    self.mdoc_end_macro(&mut line);
+                    line.delimiters.push_str(value);
+                }
+                TroffToken::TextWord if OPENING_DELIMITERS.contains(&value) => {
+                    self.consume();
+                    self.mdoc_flush_delimiters(&mut line);
+                    if line.space {
+                        self.add_to_output(SPACE);
+                    }
+                    self.add_to_output(value);
+                    line.space = false;
+                }
+                _ => self.mdoc_word(&mut line),
+            }
+        }
+
+        self.mdoc_end_macro(&mut line);
+
+        while let Some(closer) = line.closers.pop() {
+            self.add_to_output(&closer);
+        }
+
+        let delimiters = line.delimiters.clone();
+        self.add_to_output(&delimiters);
+        self.add_to_output(SPACE);
+    }
+
+    /// .Bl -type [-width val] [-offset val] [-compact] [col ...]
+    /// Begins a list, whose items each begin with .It
+    pub(super) fn parse_bl(&mut self) {
+        self.consume_val(".Bl");
+
+        let mut kind = ListKind::Item;
+        let mut width = None;
+        let mut offset = 0;
+        let mut compact = false;
+        let mut columns = Vec::new();
+
+        let args = self.mdoc_block_args();
+        let mut args_iter = args.iter();
+
+        while let Some(arg) = args_iter.next() {
+            match arg.as_str() {
+                "-width" => {
+                    width = args_iter
+                        .next()
+                        .map(|w| scaled_width(w).unwrap_or_else(|| display_width(w) + 2))
+                }
+                "-offset" => {
+                    offset = args_iter
+                        .next()
+                        .map(|o| scaled_width(o).unwrap_or_else(|| display_width(o)))
+                        .unwrap_or(0)
+                }
+                "-compact" => compact = true,
+                a if ListKind::from_arg(a).is_some() => kind = ListKind::from_arg(a).unwrap(),
+
+                // the rest of a column list's args are its columns
+                a if kind == ListKind::Column => {
+                    columns.push(scaled_width(a).unwrap_or_else(|| display_width(a)))
+                }
+                _ => {}
+            }
+        }
+
+        if compact {
+            self.add_linebreak_single();
+        } else {
+            self.mdoc_paragraph_break();
+        }
+
+        let block = MdocBlock {
+            list: Some(kind),
+            width: width.unwrap_or_else(|| kind.default_width()),
+            compact,
+            items: 0,
+            columns,
+            indent: 0,
+            nofill: false,
+        };
+
+        self.mdoc_begin_block(block, offset);
+    }
+
+    /// .It [tag]
+    /// Begins an item of the current list.
+    /// How the item begins depends on the kind of list, i.e. with a tag, a bullet or a number.
+    pub(super) fn parse_it(&mut self) {
+        let (kind, width, compact, number) = match self.mdoc_blocks.last_mut() {
+            Some(ref mut block) if block.list.is_some() => {
+                block.items += 1;
+                (block.list.unwrap(), block.width, block.compact, block.items)
+            }
+            _ => {
+                // not within a list
+                self.consume_val(".It");
+                return;
+            }
+        };
+
+        if kind.is_tagged() {
+            self.begin_tagged_paragraph();
+        }
+
+        self.consume_val(".It");
+
+        self.term_writer.set_indent(0);
+
+        if compact {
+            self.add_linebreak_single();
+        } else {
+            self.mdoc_paragraph_break();
+        }
+
+        // the end of a nested list may have left the line indented for the body of the last item
+        self.term_writer.reset_whitespace_to_startpos();
+
+        self.consume_spaces();
+
+        match kind {
+            ListKind::Tag | ListKind::Hang | ListKind::Ohang | ListKind::Inset => {
+                self.begin_tag_capture();
+                self.parse_mdoc_words(None);
+                self.end_tag_capture();
+
+                if let Some(ref mut paragraph) = self.current_paragraph {
+                    paragraph.tag_is_macro_arg = true;
+                }
+
+                match kind {
+                    ListKind::Tag => self.mdoc_pad_to_width(width, true),
+                    ListKind::Hang => self.mdoc_pad_to_width(width, false),
+                    ListKind::Ohang => self.add_linebreak_single(),
+
+                    // the body of an inset item follows its tag on the same line
+                    _ => {}
+                }
+            }
+            ListKind::Bullet | ListKind::Dash | ListKind::Enum => {
+                let marker = match kind {
+                    ListKind::Bullet => glyph("bu", self.ascii_glyphs),
+                    ListKind::Dash => "-".to_owned(),
+                    _ => format!("{}.", number),
+                };
+
+                self.add_to_output(&marker);
+                self.mdoc_pad_to_width(width, true);
+            }
+            ListKind::Column => self.parse_mdoc_words(None),
+            ListKind::Item => {}
+        }
+    }
+
+    /// .El
+    /// Ends the current list.
+    pub(super) fn parse_el(&mut self) {
+        self.consume_val(".El");
+        self.consume_line();
+
+        if self.mdoc_blocks.last().is_some_and(|b| b.list.is_some()) {
+            self.mdoc_end_block();
+        }
+    }
+
+    /// .Bd -type [-offset val] [-compact]
+    /// Begins a display, a block of text set apart from the text around it.
+    /// -literal and -unfilled displays are written line for line, as with .nf
+    pub(super) fn parse_bd(&mut self) {
+        self.consume_val(".Bd");
+
+        let mut nofill = false;
+        let mut offset = 0;
+        let mut compact = false;
+
+        let args = self.mdoc_block_args();
+        let mut args_iter = args.iter();
+
+        while let Some(arg) = args_iter.next() {
+            match arg.as_str() {
+                "-literal" | "-unfilled" => nofill = true,
+                "-filled" | "-ragged" | "-centered" => nofill = false,
+                "-offset" => {
+                    offset = args_iter
+                        .next()
+                        .map(|o| scaled_width(o).unwrap_or_else(|| display_width(o)))
+                        .unwrap_or(0)
+                }
+                "-compact" => compact = true,
+                _ => {}
+            }
+        }
+
+        if compact {
+            self.add_linebreak_single();
+        } else {
+            self.mdoc_paragraph_break();
+        }
+
+        let block = MdocBlock {
+            list: None,
+            width: 0,
+            compact,
+            items: 0,
+            columns: Vec::new(),
+            indent: 0,
+            nofill: false,
+        };
+
+        self.mdoc_begin_block(block, offset);
+
+        if nofill {
+            self.term_writer.enable_nofill();
+        } else {
+            self.term_writer.disable_nofill();
+        }
+    }
+
+    /// .Ed
+    /// Ends the current display.
+    pub(super) fn parse_ed(&mut self) {
+        self.consume_val(".Ed");
+        self.consume_line();
+
+        if self.mdoc_blocks.last().is_some_and(|b| b.list.is_none()) {
+            self.mdoc_end_block();
+        }
+    }
+
+    /// Every argument on the rest of the macro line, as plain strings.
+    fn mdoc_block_args(&mut self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        loop {
+            let arg = self.parse_macro_arg();
+            if arg.is_empty() {
+                return args;
+            }
+
+            args.push(arg.iter().map(|tok| tok.value.as_str()).collect());
+        }
+    }
+
+    /// Open a list or display, moving the margin to the current indent plus the offset.
+    fn mdoc_begin_block(&mut self, mut block: MdocBlock, offset: usize) {
+        block.indent = self.term_writer.indent();
+        block.nofill = self.term_writer.is_nofill();
+
+        self.term_writer.increase_margin(block.indent + offset);
+        self.term_writer.set_indent(0);
+
+        self.mdoc_blocks.push(block);
+    }
+
+    /// Close the innermost list or display, restoring the layout from before it began.
+    fn mdoc_end_block(&mut self) {
+        if let Some(block) = self.mdoc_blocks.pop() {
+            self.term_writer.pop_margin();
+            self.term_writer.set_indent(block.indent);
+
+            if block.nofill {
+                self.term_writer.enable_nofill();
+            } else {
+                self.term_writer.disable_nofill();
+            }
+
+            self.add_linebreak_single();
+        }
+    }
+
+    /// Begin a new line, with a blank line before it unless there already is one.
+    fn mdoc_paragraph_break(&mut self) {
+        self.add_linebreak_single();
+
+        if !self.term_writer.ends_with_blank_line() {
+            self.add_linebreak();
+        }
+    }
+
+    /// After an item's tag (or bullet), set the indent of the item's body,
+    /// padding the tag out to it if the tag is shorter.
+    /// If the tag is longer, either the body begins on the next line ('wrap'),
+    /// or it simply follows the tag.
+    fn mdoc_pad_to_width(&mut self, width: usize, wrap: bool) {
+        let target = self.term_writer.margin() + width;
+        let line_len = self.term_writer.line_len();
+
+        self.term_writer.set_indent(width);
+
+        if line_len < target {
+            for _ in line_len..target {
+                self.add_to_output(SPACE);
+            }
+        } else if wrap {
+            self.add_linebreak();
+        }
+    }
+
+    fn in_column_list(&self) -> bool {
+        match self.mdoc_blocks.last() {
+            Some(block) => block.list == Some(ListKind::Column),
+            None => false,
+        }
+    }
+
+    /// Move to the next cell of a column list's item.
+    fn mdoc_next_column(&mut self, line: &mut MdocLine) {
+        self.mdoc_end_macro(line);
+        self.mdoc_flush_delimiters(line);
+
+        line.column += 1;
+
+        let start: usize = match self.mdoc_blocks.last() {
+            Some(block) => block
+                .columns
+                .iter()
+                .take(line.column)
+                .map(|width| width + COLUMN_GAP)
+                .sum(),
+            None => 0,
+        };
+
+        let target = self.term_writer.margin() + start;
+        let line_len = self.term_writer.line_len();
+
+        let count = if line_len < target {
+            target - line_len
+        } else {
+            1
+        };
+
+        for _ in 0..count {
+            self.add_to_output(SPACE);
+        }
+
+        line.space = false;
+    }
+
+    /// Call a macro within an mdoc line, so it decides how the following words are written.
+    fn mdoc_call(&mut self, name: &str, line: &mut MdocLine) {
+        line.current = name.to_owned();
+        line.words = 0;
+
+        if name == "Ns" {
+            // 'no space' between the words on either side
+            self.mdoc_flush_delimiters(line);
+            line.space = false;
+        } else if let Some((opener, closer)) = enclosure(name, self.ascii_glyphs) {
+            self.mdoc_flush_delimiters(line);
+            if line.space {
+                self.add_to_output(SPACE);
+            }
+
+            self.add_to_output(&opener);
+            line.closers.push(closer);
+            line.space = false;
+        }
+    }
+
+    /// Finish the current macro of the line.
+    /// Some macros write a default when they weren't given any words.
+    fn mdoc_end_macro(&mut self, line: &mut MdocLine) {
+        if line.words == 0 {
+            let default = match line.current.as_str() {
+                "Fl" => Some("-".to_owned()),
+                "Ar" => Some("file ...".to_owned()),
+                "Nm" => self.mdoc_name.clone(),
+                _ => None,
+            };
+
+            if let Some(default) = default {
+                self.mdoc_write_styled(&default, line);
+            }
+        }
+
+        line.current.clear();
+        line.words = 0;
+    }
+
+    /// Write out punctuation held back in case it ended the line.
+    fn mdoc_flush_delimiters(&mut self, line: &mut MdocLine) {
+        if !line.delimiters.is_empty() {
+            let delimiters = line.delimiters.clone();
+            self.add_to_output(&delimiters);
+            line.delimiters.clear();
+            line.space = true;
+        }
+    }
+
+    /// Write some text in the style of the current macro.
+    fn mdoc_write_styled(&mut self, text: &str, line: &mut MdocLine) {
+        self.mdoc_flush_delimiters(line);
+        if line.space {
+            self.add_to_output(SPACE);
+        }
+
+        let style = macro_style(&line.current);
+        if let Some(style) = style {
+            self.term_writer.set_fontstyle(style);
+        }
+
+        self.add_to_output(text);
+
+        if let Some(style) = style {
+            self.term_writer.unset_fontstyle(style);
+        }
+
+        line.space = true;
+    }
+
+    /// Write the word at the current token, as decided by the current macro of the line.
+    fn mdoc_word(&mut self, line: &mut MdocLine) {
+        self.mdoc_flush_delimiters(line);
+
+        // '.Xr ls 1' is written 'ls(1)'
+        let is_xr_section = line.current == "Xr" && line.words == 1;
+
+        if line.space && !is_xr_section {
+            self.add_to_output(SPACE);
+        }
+
+        let style = macro_style(&line.current);
+        if let Some(style) = style {
+            self.term_writer.set_fontstyle(style);
+        }
+
+        if is_xr_section {
+            self.add_to_output("(");
+        } else if line.current == "Fl" {
+            self.add_to_output("-");
+        }
+
+        let word = self.mdoc_parse_word();
+
+        if is_xr_section {
+            self.add_to_output(")");
+        }
+
+        if let Some(style) = style {
+            self.term_writer.unset_fontstyle(style);
+        }
+
+        if line.current == "Nm" && self.mdoc_name.is_none() {
+            self.mdoc_name = Some(word);
+        }
+
+        line.words += 1;
+        line.space = true;
+    }
+
+    /// Parse and output a single word of a macro line, which may be quoted.
+    /// Returns the text of the word, as written.
+    fn mdoc_parse_word(&mut self) -> String {
+        let mut text = String::new();
+
+        if let Some(tok) = self.current_token() {
+            if tok.class == TroffToken::DoubleQuote {
+                for tok in self.get_within_quotes() {
+                    self.add_to_output(&tok.value);
+                    text.push_str(&tok.value);
+                }
+
+                return text;
+            }
+        }
+
+        while let Some(tok) = self.current_token() {
+            if tok.starts_line || tok.class == TroffToken::Whitespace {
+                break;
+            }
+
+            if tok.class == TroffToken::TextWord {
+                text.push_str(&tok.value);
+            }
+
+            self.parse_word();
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glyph;
+    use man_parse::troff_parser::{render, render_tokens, TroffParser};
+    use man_parse::troff_token_generator::TroffTokenGenerator;
+    use simple_parser::tokenizer::tokenize;
+
+    #[test]
+    fn test_inline_macros() {
+        let page = ".Nm split\n.Op Fl a Ar suffix_length\n.Op Ar file Op Ar prefix\n";
+
+        assert_eq!(render(page), "split [-a suffix_length] [file [prefix]]");
+    }
+
+    #[test]
+    fn test_escaped_macro_names_are_text() {
+        assert_eq!(render(".Op \\&Fl s \\&Ar bytes\n"), "[Fl s Ar bytes]");
+    }
+
+    #[test]
+    fn test_delimiters_and_cross_references() {
+        let page = ".Pq see Xr ls 1 .\n.Ev HOME , Pa /etc/passwd ;\n.Fl \\-color Ns = Ns Ar when\n";
+
+        assert_eq!(render(page), "(see ls(1)). HOME, /etc/passwd; --color=when");
+    }
+
+    #[test]
+    fn test_name_section() {
+        let page = ".Dd $Mdocdate$\n.Dt LS 1\n.Os\n.Sh NAME\n.Nm ls\n.Nd list directory contents\n.Sh SYNOPSIS\n.Nm\n.Op Fl l\n";
+        let rendered = render(page);
+
+        assert!(rendered.contains("ls - list directory contents"));
+        assert!(rendered.ends_with("ls [-l]"));
+        assert!(!rendered.contains("skipping"));
+    }
+
+    #[test]
+    fn test_tag_list() {
+        let page = ".Bl -tag -width Ds\n.It Fl a\nall entries\n.It Fl \\-color Ns = Ns Ar when\ncolorize\n.El\n";
+
+        assert_eq!(
+            render(page),
+            "-a    all entries\n\n--color=when\n      colorize"
+        );
+    }
+
+    #[test]
+    fn test_bullet_enum_and_nested_lists() {
+        let page =
+            ".Bl -enum -compact\n.It\none\n.Bl -dash -compact\n.It\ninner\n.El\n.It\ntwo\n.El\n";
+
+        assert_eq!(render(page), "1.  one\n    - inner\n2.  two");
+    }
+
+    #[test]
+    fn test_column_list() {
+        let page =
+            ".Bl -column \"name\" \"value\" -compact\n.It Sy key Ta Sy value\n.It a Ta b\n.El\n";
+
+        assert_eq!(render(page), "key   value\na     b");
+    }
+
+    #[test]
+    fn test_literal_display() {
+        let page = "text\n.Bd -literal -offset indent\n$ ls  -l\n$ pwd\n.Ed\nmore\n";
+
+        assert_eq!(render(page), "text\n\n      $ ls  -l\n      $ pwd\nmore");
+    }
+
+    #[test]
+    fn test_option_entries_from_tag_list() {
+        let page = ".Sh OPTIONS\n.Bl -tag -width Ds\n.It Fl f Ar file\nread all but\n.Ql \\&.\n.Bl -bullet\n.It\nnested\n.El\n.It Fl v\nverbose\n.El\n";
+        let tokens = tokenize(page, &TroffTokenGenerator {});
+
+        let mut parser = TroffParser::new();
+        parser.parse(tokens.iter());
+
+        let entries = parser.option_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].flags, vec!["-f"]);
+        assert_eq!(entries[0].placeholder.as_ref().unwrap().name, "file");
+        assert_eq!(entries[1].flags, vec!["-v"]);
+
+        // the entry renders on its own, still calling the tag's macros
+        let rendered = render_tokens(&entries[0].to_tokens());
+        assert!(rendered.contains("read"));
+        assert!(rendered.contains("nested"));
+        assert!(!rendered.contains("Fl"));
+
+        // as does its explanation, keeping the space before the line calling a macro
+        let mut parser = TroffParser::new().with_args(vec!["-f".into()]);
+        parser.parse(tokens.iter());
+
+        let ascii = parser.ascii_glyphs;
+        let quoted = format!("read all but {}.{}", glyph("oq", ascii), glyph("cq", ascii));
+        assert!(parser.result_text().contains(&quoted));
+    }
+
+    #[test]
+    fn test_ascii_glyphs() {
+        let page = ".Dq double\n.Sq single\n.Bl -bullet\n.It\nitem\n.El\n";
+        let tokens = tokenize(page, &TroffTokenGenerator {});
+
+        let mut parser = TroffParser::new();
+        parser.enable_plain_output();
+        parser.ascii_glyphs = true;
+        parser.parse(tokens.iter());
+
+        let text = parser.result_text();
+        assert!(text.contains("\"double\" `single'"));
+        assert!(text.contains("o item"));
+    }
+}
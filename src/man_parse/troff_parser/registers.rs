@@ -0,0 +1,224 @@
+//! Number registers, set with '.nr name value [increment]'
+//! and interpolated with '\nx', '\n(xx' or '\n[name]'.
+//! '\n+x' and '\n-x' increment or decrement the register before interpolating it.
+
+use man_parse::troff_parser::expression::{eval_numeric, UNITS_PER_CHAR, UNITS_PER_LINE};
+use man_parse::troff_parser::macros::strip_comment;
+use man_parse::troff_parser::TroffParser;
+use man_parse::troff_token_generator::TroffToken;
+use simple_parser::token::Token;
+
+/// A number register defined by the page.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Register {
+    value: i32,
+
+    /// How much '\n+x' and '\n-x' change the value by
+    increment: i32,
+}
+
+impl<'a, I> TroffParser<'a, I>
+where
+    I: Iterator<Item = &'a Token<TroffToken>>,
+{
+    /// Request: .nr name value [increment]
+    /// Set a register, where the value is a numeric expression.
+    /// A value beginning with '+' or '-' is added to or subtracted from the current value.
+    /// Built-in registers, like '.g', are read-only, so can't be set.
+    pub(super) fn parse_nr(&mut self) {
+        self.consume();
+
+        let name = self.parse_request_name();
+        let line = strip_comment(&self.parse_request_line()).to_owned();
+        let line = self.interpolate_text(&line);
+
+        let mut words = line.split_whitespace();
+        let expr = match words.next() {
+            Some(expr) => expr,
+            None => return,
+        };
+
+        if name.is_empty() || name.starts_with('.') {
+            return;
+        }
+
+        let register = self.registers.entry(name).or_default();
+
+        if let Some(value) = eval_numeric(expr, 'u') {
+            if expr.starts_with('+') || expr.starts_with('-') {
+                register.value = register.value.saturating_add(value);
+            } else {
+                register.value = value;
+            }
+        }
+
+        if let Some(increment) = words.next().and_then(|incr| eval_numeric(incr, 'u')) {
+            register.increment = increment;
+        }
+    }
+
+    /// Request: .rr name
+    /// Remove the register with the given name.
+    pub(super) fn parse_rr(&mut self) {
+        self.consume();
+
+        let line = strip_comment(&self.parse_request_line()).to_owned();
+
+        for name in line.split_whitespace() {
+            self.registers.remove(name);
+        }
+    }
+
+    /// Read the sign of an escape like '\n+x', if it has one.
+    pub(super) fn interpolation_sign(&mut self) -> Option<char> {
+        let tok = self.next_raw_token()?;
+
+        if tok.class == TroffToken::CommandArg && (tok.value == "+" || tok.value == "-") {
+            return tok.value.chars().next();
+        }
+
+        self.pending_tokens.push_front(tok);
+        None
+    }
+
+    /// The value of a register, as it's interpolated.
+    /// A sign of '+' or '-' first changes the register by its increment.
+    /// As in troff, an undefined register is 0.
+    pub(super) fn register_value(&mut self, name: &str, sign: Option<char>) -> String {
+        if let Some(value) = self.builtin_register(name) {
+            return value.to_string();
+        }
+
+        let register = self.registers.entry(name.to_owned()).or_default();
+
+        match sign {
+            Some('+') => register.value = register.value.saturating_add(register.increment),
+            Some('-') => register.value = register.value.saturating_sub(register.increment),
+            _ => {}
+        }
+
+        register.value.to_string()
+    }
+
+    /// The read-only registers troff defines, which pages test to see how they're formatted.
+    pub(super) fn builtin_register(&self, name: &str) -> Option<i32> {
+        let value = match name {
+            // groff's extensions are available
+            ".g" => 1,
+            // writing to a device given with -T, the terminal
+            ".T" => 1,
+            // args of the current macro, though calls have this substituted already
+            ".$" => 0,
+            ".l" => self.term_writer.max_line_length() as i32 * UNITS_PER_CHAR,
+            ".i" => (self.term_writer.margin() + self.term_writer.indent()) as i32 * UNITS_PER_CHAR,
+            ".v" => UNITS_PER_LINE,
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Interpolate the registers and strings in the text of a request,
+    /// i.e. the '\n(.l' in '.nr x \n(.l/2'
+    pub(super) fn interpolate_text(&mut self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(i) = rest.find('\\') {
+            result.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+
+            let command = match rest.chars().next() {
+                Some(command) => command,
+                None => {
+                    result.push('\\');
+                    break;
+                }
+            };
+            rest = &rest[command.len_utf8()..];
+
+            match command {
+                'n' => {
+                    let sign = rest.chars().next().filter(|&c| c == '+' || c == '-');
+                    if sign.is_some() {
+                        rest = &rest[1..];
+                    }
+
+                    let (name, len) = escape_name(rest);
+                    rest = &rest[len..];
+
+                    let value = self.register_value(&name, sign);
+                    result.push_str(&value);
+                }
+                '*' => {
+                    let (name, len) = escape_name(rest);
+                    rest = &rest[len..];
+
+                    if let Some(value) = self.strings.get(&name) {
+                        result.push_str(value);
+                    }
+                }
+                _ => {
+                    result.push('\\');
+                    result.push(command);
+                }
+            }
+        }
+
+        result.push_str(rest);
+        result
+    }
+}
+
+/// Read the name following an escape in text, in any of its three forms:
+/// 'x', '(xx' or '[name]'. Returns the name, and the length of text it took up.
+fn escape_name(text: &str) -> (String, usize) {
+    let mut chars = text.chars();
+
+    match chars.next() {
+        Some('(') => {
+            let name: String = chars.take(2).collect();
+            let len = 1 + name.len();
+            (name, len)
+        }
+        Some('[') => match text.find(']') {
+            Some(close) => (text[1..close].to_owned(), close + 1),
+            None => (text[1..].to_owned(), text.len()),
+        },
+        Some(c) => (c.to_string(), c.len_utf8()),
+        None => (String::new(), 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use man_parse::troff_parser::render;
+
+    #[test]
+    fn test_register_forms() {
+        let page = ".nr x 1\n.nr yy 22\n.nr long 333\n\\nx \\n(yy \\n[long] \\n[undefined]\n";
+
+        assert_eq!(render(page), "1 22 333 0");
+    }
+
+    #[test]
+    fn test_relative_and_expressions() {
+        let page = ".nr x 10\n.nr x +5\n.nr y \\nx*2\n.nr z (1+2)*3\n\\nx \\ny \\nz\n";
+
+        assert_eq!(render(page), "15 30 9");
+    }
+
+    #[test]
+    fn test_auto_increment() {
+        let page = ".nr step 0 2\n\\n+[step] \\n+[step] \\n-[step] \\n[step]\n";
+
+        assert_eq!(render(page), "2 4 2 2");
+    }
+
+    #[test]
+    fn test_builtin_and_removed_registers() {
+        let page = ".nr .g 0\n.nr x 5\n.rr x\n\\n(.g \\n(.v \\nx\n";
+
+        assert_eq!(render(page), "1 40 0");
+    }
+}
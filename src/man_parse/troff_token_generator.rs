@@ -3,6 +3,7 @@
 ///
 use simple_parser::token::{Token, TokenClass};
 use simple_parser::token_generator::TokenGenerator;
+use simple_parser::tokenizer::tokenize;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum TroffToken {
@@ -34,6 +35,38 @@ const EMPTY: &str = "";
 
 pub struct TroffTokenGenerator;
 
+/// Tokenize the text of a whole page.
+/// Requests and macros may be indented after their control character, like '.    ds x y',
+/// so that indentation is removed first.
+/// The no-break control character, as in "'br", is treated the same as '.'
+pub fn tokenize_page(text: &str) -> Vec<Token<TroffToken>> {
+    let mut normalized = String::with_capacity(text.len());
+
+    for line in text.lines() {
+        if let Some(request) = line.strip_prefix('.').or_else(|| line.strip_prefix('\'')) {
+            let request = request.trim_start();
+
+            // a request's name may run into an escape, like the '\{' of '.el\{'
+            let name_len = request
+                .find(|c: char| c.is_whitespace() || c == '\\')
+                .unwrap_or(request.len());
+
+            normalized.push('.');
+            normalized.push_str(&request[..name_len]);
+            if name_len > 0 && request[name_len..].starts_with('\\') {
+                normalized.push(' ');
+            }
+            normalized.push_str(&request[name_len..]);
+        } else {
+            normalized.push_str(line);
+        }
+
+        normalized.push('\n');
+    }
+
+    tokenize(&normalized, &TroffTokenGenerator {})
+}
+
 impl TokenGenerator<TroffToken> for TroffTokenGenerator {
     fn generate(&self, word: &str, starts_line: bool) -> Vec<Token<TroffToken>> {
         let mut tokens = Vec::new(); // TODO: preallocate a smart amount
@@ -56,11 +89,16 @@ impl TokenGenerator<TroffToken> for TroffTokenGenerator {
         }
 
         let mut starts_line = starts_line;
-        let mut char_iter = word.chars().enumerate();
+        let mut char_iter = word.char_indices();
 
-        let mut base_index: usize = 0; // index of starting position of current token within word
+        let mut base_index: usize = 0; // byte index of starting position of current token within word
 
         while let Some((c_index, c)) = char_iter.next() {
+            // the chars of an escape's args are already part of its tokens
+            if c_index < base_index {
+                continue;
+            }
+
             if let Some(special_class) = try_match_special(&c) {
                 // Encountered special char. Some chars are special chars that are tokens on their own,
                 // and appear in the middle of words, so in this case we split the word into multiple tokens.
@@ -90,6 +128,7 @@ impl TokenGenerator<TroffToken> for TroffTokenGenerator {
                     };
 
                     let (next_index, escaped_char) = next;
+                    let after_escaped = next_index + escaped_char.len_utf8();
 
                     if escaped_char == '(' {
                         // '(' is a special case; it isn't a command,
@@ -103,11 +142,11 @@ impl TokenGenerator<TroffToken> for TroffTokenGenerator {
                         tokens.push(escaped_char_tok);
 
                         // the part following '(', like 'cq'
-                        let endpoint = next_index + 3;
-                        let encoding = &word[next_index + 1..endpoint];
-                        let encoded_tok = Token::new(TroffToken::TextWord, encoding.into(), false);
+                        let encoding: String = word[after_escaped..].chars().take(2).collect();
+                        base_index = after_escaped + encoding.len();
+
+                        let encoded_tok = Token::new(TroffToken::TextWord, encoding, false);
                         tokens.push(encoded_tok);
-                        base_index = endpoint;
 
                         continue;
                     }
@@ -116,29 +155,27 @@ impl TokenGenerator<TroffToken> for TroffTokenGenerator {
                         Token::new(TroffToken::EscapeCommand, escaped_char.to_string(), false);
 
                     tokens.push(escaped_tok);
-                    base_index = next_index + 1;
+                    base_index = after_escaped;
 
                     // after the escaped token, we might now have an argument
                     // i.e., '\fB' takes arg 'B'
-                    if !command_has_args(escaped_char) {
-                        continue;
-                    }
-
-                    let possible_args = &word[next_index + 1..];
-
-                    let args = get_escaped_args(possible_args);
-                    let mut advance_count = 0;
+                    let possible_args = &word[after_escaped..];
+
+                    let args = match escaped_char {
+                        // a special character with a long name, like '\[em]'
+                        '[' => get_bracketed_name(possible_args),
+                        // a special character named between delimiters, like '\C'em''
+                        'C' => get_delimited_arg(possible_args),
+                        // a point size, which may be relative, like '\s-2'
+                        's' => get_size_args(possible_args),
+                        c if command_has_args(c) => get_escaped_args(possible_args),
+                        _ => continue,
+                    };
 
                     for arg in args {
-                        advance_count += arg.value.len();
+                        base_index += arg.value.len();
                         tokens.push(arg);
                     }
-
-                    for _ in 0..advance_count {
-                        char_iter.next();
-                    }
-
-                    base_index += advance_count;
                 }
             }
         }
@@ -174,7 +211,7 @@ impl TokenGenerator<TroffToken> for TroffTokenGenerator {
 /// so it does not have args.
 fn command_has_args(command: char) -> bool {
     match command {
-        'f' | 'm' | 's' => true,
+        'f' | 'm' | 'M' | 'n' | 's' | '*' => true,
         _ => false,
     }
 }
@@ -186,6 +223,7 @@ fn command_has_args(command: char) -> bool {
 /// c -> c
 /// (co -> (, co
 /// [color] -> [, color, ]
+/// +(co -> +, (, co
 fn get_escaped_args(word: &str) -> Vec<Token<TroffToken>> {
     let mut v = Vec::new();
 
@@ -200,43 +238,35 @@ fn get_escaped_args(word: &str) -> Vec<Token<TroffToken>> {
             let open_tok = Token::new(TroffToken::ArgOpenParen, "(".to_owned(), false);
             v.push(open_tok);
 
-            let arg = &word[1..3];
-            let arg_tok = Token::new(TroffToken::CommandArg, arg.to_owned(), false);
+            let arg: String = word.chars().skip(1).take(2).collect();
+            let arg_tok = Token::new(TroffToken::CommandArg, arg, false);
             v.push(arg_tok);
         }
         Some('[') => {
             let open_tok = Token::new(TroffToken::ArgOpenBracket, "[".to_owned(), false);
             v.push(open_tok);
 
-            let close_index = word.find(']').unwrap();
-            let arg = &word[1..close_index];
+            // an unclosed bracket takes the rest of the word
+            let close_index = word.find(']');
+            let arg = &word[1..close_index.unwrap_or(word.len())];
             let arg_tok = Token::new(TroffToken::CommandArg, arg.to_owned(), false);
             v.push(arg_tok);
 
-            let close_tok = Token::new(TroffToken::ArgCloseBracket, "]".to_owned(), false);
-            v.push(close_tok);
-        }
-        Some('-') => {
-            // TODO: \s+(34 is allowed
-            let sign_tok = Token::new(TroffToken::CommandArg, "-".to_owned(), false);
-            v.push(sign_tok);
-
-            let num = word.chars().nth(1).unwrap().to_string();
-            let num_tok = Token::new(TroffToken::CommandArg, num, false);
-            v.push(num_tok);
+            if close_index.is_some() {
+                let close_tok = Token::new(TroffToken::ArgCloseBracket, "]".to_owned(), false);
+                v.push(close_tok);
+            }
         }
-        Some('+') => {
-            // TODO: \s+(34 is allowed
-            let sign_tok = Token::new(TroffToken::CommandArg, "+".to_owned(), false);
+        Some(sign @ '-') | Some(sign @ '+') => {
+            // a sign comes before the arg proper,
+            // i.e. the size of '\s-2' or the register of '\n+(xx'
+            let sign_tok = Token::new(TroffToken::CommandArg, sign.to_string(), false);
             v.push(sign_tok);
 
-            let num = word.chars().nth(1).unwrap().to_string();
-            let num_tok = Token::new(TroffToken::CommandArg, num, false);
-            v.push(num_tok);
+            v.extend(get_escaped_args(&word[1..]));
         }
-        Some(_) => {
-            let arg = &word[0..1];
-            let arg_tok = Token::new(TroffToken::CommandArg, arg.to_owned(), false);
+        Some(c) => {
+            let arg_tok = Token::new(TroffToken::CommandArg, c.to_string(), false);
             v.push(arg_tok);
         }
         None => {}
@@ -245,6 +275,64 @@ fn get_escaped_args(word: &str) -> Vec<Token<TroffToken>> {
     v
 }
 
+/// Given a word that appears after '\s', split off the size.
+/// A sign is kept together with a single digit size,
+/// otherwise the size is split the same as any other arg.
+/// -2word -> -2
+/// +(12word -> +, (, 12
+fn get_size_args(word: &str) -> Vec<Token<TroffToken>> {
+    let mut chars = word.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(sign), Some(digit)) if (sign == '-' || sign == '+') && digit.is_ascii_digit() => {
+            let size = format!("{}{}", sign, digit);
+            vec![Token::new(TroffToken::CommandArg, size, false)]
+        }
+        _ => get_escaped_args(word),
+    }
+}
+
+/// Given a word that appears after '\[', split the name from the closing bracket.
+/// em]-- -> em, ]
+fn get_bracketed_name(word: &str) -> Vec<Token<TroffToken>> {
+    // an unclosed bracket takes the rest of the word
+    let close_index = word.find(']');
+    let name = &word[..close_index.unwrap_or(word.len())];
+
+    let mut v = vec![Token::new(TroffToken::CommandArg, name.to_owned(), false)];
+
+    if close_index.is_some() {
+        v.push(Token::new(
+            TroffToken::ArgCloseBracket,
+            "]".to_owned(),
+            false,
+        ));
+    }
+
+    v
+}
+
+/// Given a word that appears after an escaped char taking a delimited arg,
+/// take the arg along with its delimiters, which can be any char.
+/// 'em'-- -> 'em'
+fn get_delimited_arg(word: &str) -> Vec<Token<TroffToken>> {
+    let delimiter = match word.chars().next() {
+        Some(delimiter) => delimiter,
+        None => return Vec::new(),
+    };
+
+    let start = delimiter.len_utf8();
+    let end = word[start..]
+        .find(delimiter)
+        .map_or(word.len(), |i| start + i + delimiter.len_utf8());
+
+    vec![Token::new(
+        TroffToken::CommandArg,
+        word[..end].to_owned(),
+        false,
+    )]
+}
+
 fn try_match_special(c: &char) -> Option<TroffToken> {
     let special_tok = match *c {
         '\\' => Some(TroffToken::Backslash),
@@ -280,6 +368,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_special_character_names() {
+        let word = "a\\[em]b\\C'bu'–\\(->c";
+        let generator = TroffTokenGenerator {};
+
+        let actual = generator.generate(word, false);
+
+        let expected = vec![
+            Token::new(TroffToken::TextWord, "a".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "[".to_owned(), false),
+            Token::new(TroffToken::CommandArg, "em".to_owned(), false),
+            Token::new(TroffToken::ArgCloseBracket, "]".to_owned(), false),
+            Token::new(TroffToken::TextWord, "b".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "C".to_owned(), false),
+            Token::new(TroffToken::CommandArg, "'bu'".to_owned(), false),
+            Token::new(TroffToken::TextWord, "–".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "(".to_owned(), false),
+            Token::new(TroffToken::TextWord, "->".to_owned(), false),
+            Token::new(TroffToken::TextWord, "c".to_owned(), false),
+        ];
+
+        assert!(
+            actual == expected,
+            "expected: {:?}\nactual: {:?}",
+            expected,
+            actual
+        );
+    }
+
     #[test]
     fn test_short_word() {
         let word = "\"I\""; // "I"
@@ -379,10 +499,26 @@ mod tests {
         let expected = vec![
             Token::new(TroffToken::TextWord, "Manual".to_owned(), false),
             Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "f".to_owned(), false),
+            Token::new(TroffToken::CommandArg, "R".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
             Token::new(TroffToken::EscapeCommand, "m".to_owned(), false),
             Token::new(TroffToken::ArgOpenBracket, "[".to_owned(), false),
             Token::new(TroffToken::CommandArg, "".to_owned(), false),
             Token::new(TroffToken::ArgCloseBracket, "]".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "&".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "s".to_owned(), false),
+            Token::new(TroffToken::CommandArg, "-2".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "u".to_owned(), false),
+            Token::new(TroffToken::TextWord, "[1]".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "d".to_owned(), false),
+            Token::new(TroffToken::Backslash, "\\".to_owned(), false),
+            Token::new(TroffToken::EscapeCommand, "s".to_owned(), false),
+            Token::new(TroffToken::CommandArg, "+2".to_owned(), false),
         ];
 
         assert_eq!(actual, expected);
@@ -0,0 +1,680 @@
+use simple_parser::shell_token_generator::{
+    ShellToken, ShellTokenGenerator, ShellWord, WordPartKind,
+};
+use std::fmt;
+use std::ops::Range;
+
+/// What comes between two commands in a command line.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Separator {
+    /// '|'
+    Pipe,
+    /// '&&'
+    And,
+    /// '||'
+    Or,
+    /// ';'
+    Sequence,
+    /// '&'
+    Background,
+}
+
+impl Separator {
+    fn from_operator(operator: &str) -> Option<Self> {
+        match operator {
+            "|" => Some(Separator::Pipe),
+            "&&" => Some(Separator::And),
+            "||" => Some(Separator::Or),
+            ";" => Some(Separator::Sequence),
+            "&" => Some(Separator::Background),
+            _ => None,
+        }
+    }
+
+    pub fn operator(&self) -> &'static str {
+        match *self {
+            Separator::Pipe => "|",
+            Separator::And => "&&",
+            Separator::Or => "||",
+            Separator::Sequence => ";",
+            Separator::Background => "&",
+        }
+    }
+
+    /// A plain description of what the separator does.
+    pub fn explain(&self) -> &'static str {
+        match *self {
+            Separator::Pipe => "the output of this command is the input of the next",
+            Separator::And => "the next command only runs if this one succeeds",
+            Separator::Or => "the next command only runs if this one fails",
+            Separator::Sequence => "the next command runs after this one finishes",
+            Separator::Background => "this command runs in the background",
+        }
+    }
+}
+
+/// A redirection of a command's input or output, such as '> out.txt' or '2>&1'.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Redirection {
+    /// The file descriptor being redirected, if given explicitly, i.e. the '2' in '2>&1'
+    pub fd: Option<u32>,
+
+    /// The operator, i.e. '>', '>>', '<', '>&'
+    pub operator: String,
+
+    /// The file (or file descriptor) redirected to or from.
+    pub target: String,
+}
+
+impl Redirection {
+    /// A plain description of what the redirection does.
+    pub fn explain(&self) -> String {
+        let default_fd = if self.operator.starts_with('<') { 0 } else { 1 };
+        let stream = match self.fd.unwrap_or(default_fd) {
+            0 => "standard input".to_owned(),
+            1 => "standard output".to_owned(),
+            2 => "standard error".to_owned(),
+            fd => format!("file descriptor {}", fd),
+        };
+
+        match self.operator.as_str() {
+            ">" => format!("write {} to the file {}", stream, self.target),
+            ">|" => format!(
+                "write {} to the file {}, even if it exists and noclobber is set",
+                stream, self.target
+            ),
+            ">>" => format!("append {} to the file {}", stream, self.target),
+            "<" => format!("read {} from the file {}", stream, self.target),
+            "<>" => format!(
+                "open the file {} for reading and writing as {}",
+                self.target, stream
+            ),
+            "<<" | "<<-" => format!(
+                "read {} from the following lines, up to a line containing {}",
+                stream, self.target
+            ),
+            "<<<" => format!("read {} from the string {}", stream, self.target),
+            "&>" => format!(
+                "write standard output and standard error to the file {}",
+                self.target
+            ),
+            "&>>" => format!(
+                "append standard output and standard error to the file {}",
+                self.target
+            ),
+            ">&" | "<&" if self.target == "-" => format!("close {}", stream),
+            ">&" | "<&" => format!(
+                "send {} to wherever {} goes",
+                stream,
+                describe_fd(&self.target)
+            ),
+            _ => format!("redirect {} with {}", stream, self.operator),
+        }
+    }
+}
+
+impl fmt::Display for Redirection {
+    /// The redirection as it would be written, i.e. '2>&1' or '> out.txt'
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(fd) = self.fd {
+            write!(f, "{}", fd)?;
+        }
+
+        if self.operator.ends_with('&') {
+            write!(f, "{}{}", self.operator, self.target)
+        } else {
+            write!(f, "{} {}", self.operator, self.target)
+        }
+    }
+}
+
+fn describe_fd(fd: &str) -> String {
+    match fd {
+        "0" => "standard input".to_owned(),
+        "1" => "standard output".to_owned(),
+        "2" => "standard error".to_owned(),
+        _ => format!("file descriptor {}", fd),
+    }
+}
+
+/// A single command within a command line,
+/// i.e. 'grep -n foo > out.txt' in 'cat file | grep -n foo > out.txt'
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SimpleCommand {
+    /// Variable assignments preceding the command, like 'LANG=C'
+    pub assignments: Vec<String>,
+
+    /// The command name followed by its arguments, with quoting removed.
+    pub words: Vec<String>,
+
+    pub redirections: Vec<Redirection>,
+
+    /// How this command connects to the next one, if there is a next one.
+    pub separator: Option<Separator>,
+
+    /// The expansions within the command's words, like $HOME or $(date).
+    pub expansions: Vec<Expansion>,
+
+    /// Where the command is in the command line, as byte offsets,
+    /// from its first word up to its last word or redirection.
+    pub span: Range<usize>,
+}
+
+impl SimpleCommand {
+    /// The command's words split into the commands it runs, each as a name and its own args,
+    /// i.e. 'sudo nice -n 5 make' is 'sudo', then 'nice -n 5', then 'make'.
+    pub fn command_chain(&self) -> Vec<&[String]> {
+        let mut chain = Vec::new();
+        let mut words = self.words.as_slice();
+
+        while let Some((name, args)) = words.split_first() {
+            match nested_command_start(name, args) {
+                Some(start) => {
+                    chain.push(&words[..start + 1]);
+                    words = &args[start..];
+                }
+                None => {
+                    chain.push(words);
+                    break;
+                }
+            }
+        }
+
+        chain
+    }
+
+    fn is_empty(&self) -> bool {
+        self.assignments.is_empty() && self.words.is_empty() && self.redirections.is_empty()
+    }
+
+    fn extend_span(&mut self, span: &Range<usize>) {
+        if self.is_empty() && self.span.start == self.span.end {
+            self.span = span.clone();
+        } else {
+            self.span.end = span.end;
+        }
+    }
+}
+
+/// Something the shell substitutes before running a command,
+/// i.e. '$HOME', '$(date)' or the file name pattern '*.rs'
+#[derive(Debug, PartialEq, Clone)]
+pub struct Expansion {
+    pub kind: WordPartKind,
+
+    /// The expansion as written
+    pub text: String,
+
+    /// Where the expansion is in the command line, as byte offsets.
+    pub span: Range<usize>,
+}
+
+impl Expansion {
+    /// The expansions of a word. A word with any unquoted pattern chars expands
+    /// as a whole to matching file names, so it is a single expansion.
+    fn from_word(line: &str, word: &ShellWord) -> Vec<Expansion> {
+        let is_pattern = word.parts.iter().any(|p| p.kind == WordPartKind::Glob);
+
+        if is_pattern {
+            return vec![Expansion {
+                kind: WordPartKind::Glob,
+                text: line[word.span.clone()].to_owned(),
+                span: word.span.clone(),
+            }];
+        }
+
+        word.parts
+            .iter()
+            .filter(|p| p.kind != WordPartKind::Literal)
+            .map(|p| Expansion {
+                kind: p.kind,
+                text: p.value.clone(),
+                span: p.span.clone(),
+            })
+            .collect()
+    }
+
+    /// A plain description of what the expansion becomes.
+    pub fn explain(&self) -> String {
+        match self.kind {
+            WordPartKind::Parameter => describe_parameter(&self.text),
+            WordPartKind::CommandSubstitution => {
+                // the closing ')' or '`' may be missing from an unfinished command line
+                let command = match self.text.strip_prefix("$(") {
+                    Some(inner) => inner.strip_suffix(')').unwrap_or(inner),
+                    None => self.text.trim_matches('`'),
+                };
+                format!("the output of the command {}", command.trim())
+            }
+            WordPartKind::Arithmetic => {
+                let inner = self.text.strip_prefix("$((").unwrap_or(&self.text);
+                let expression = inner.strip_suffix("))").unwrap_or(inner);
+                format!(
+                    "the result of the arithmetic expression {}",
+                    expression.trim()
+                )
+            }
+            WordPartKind::Glob => format!("the names of files matching {}", self.text),
+            WordPartKind::Literal => self.text.clone(),
+        }
+    }
+}
+
+/// Describe a parameter expansion like '$HOME', '$?' or '${name:-default}'
+fn describe_parameter(text: &str) -> String {
+    let inner = text[1..].trim_start_matches('{').trim_end_matches('}');
+
+    let is_name = !inner.is_empty() && inner.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    match inner {
+        "?" => "the exit status of the last command".to_owned(),
+        "$" => "the process ID of the shell".to_owned(),
+        "!" => "the process ID of the last background command".to_owned(),
+        "#" => "the number of positional parameters".to_owned(),
+        "@" | "*" => "all of the positional parameters".to_owned(),
+        "-" => "the current shell option flags".to_owned(),
+        "0" => "the name of the shell or script".to_owned(),
+        _ if inner.chars().all(|c| c.is_ascii_digit()) && !inner.is_empty() => {
+            format!("positional parameter {}", inner)
+        }
+        _ if is_name => format!("the value of the variable {}", inner),
+        _ => format!("the value of the parameter expression {}", text),
+    }
+}
+
+/// Utilities that run the command given by their operands,
+/// along with those of their options that take the next arg as a value.
+/// i.e. in 'xargs -n 1 grep -n foo', '-n 1' is for xargs, and 'grep -n foo' is the command it runs.
+const COMMAND_RUNNERS: &[(&str, &[&str])] = &[
+    (
+        "xargs",
+        &[
+            "-a",
+            "-d",
+            "-E",
+            "-I",
+            "-L",
+            "-n",
+            "-P",
+            "-s",
+            "--arg-file",
+            "--delimiter",
+            "--max-args",
+            "--max-chars",
+            "--max-lines",
+            "--max-procs",
+            "--process-slot-var",
+        ],
+    ),
+    (
+        "env",
+        &["-C", "-S", "-u", "--chdir", "--split-string", "--unset"],
+    ),
+    (
+        "sudo",
+        &[
+            "-C",
+            "-D",
+            "-g",
+            "-p",
+            "-r",
+            "-R",
+            "-t",
+            "-T",
+            "-u",
+            "-U",
+            "--chdir",
+            "--chroot",
+            "--close-from",
+            "--command-timeout",
+            "--group",
+            "--other-user",
+            "--prompt",
+            "--role",
+            "--type",
+            "--user",
+        ],
+    ),
+    ("nice", &["-n", "--adjustment"]),
+    ("nohup", &[]),
+    ("timeout", &["-k", "-s", "--kill-after", "--signal"]),
+    ("time", &["-f", "-o", "--format", "--output"]),
+];
+
+/// Where the command run by the named utility starts in its args,
+/// if it's a utility that runs a command, and one is given.
+/// Before the command come the utility's options and their values,
+/// and for env its variable assignments, and for timeout its duration.
+pub fn nested_command_start(name: &str, args: &[String]) -> Option<usize> {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    let &(_, value_options) = COMMAND_RUNNERS.iter().find(|(runner, _)| *runner == name)?;
+
+    let mut leading_operands = if name == "timeout" { 1 } else { 0 };
+    let mut options_ended = false;
+    let mut index = 0;
+
+    while let Some(arg) = args.get(index) {
+        if !options_ended && arg == "--" {
+            options_ended = true;
+        } else if !options_ended && arg.starts_with('-') && arg.len() > 1 {
+            if value_options.contains(&arg.as_str()) {
+                index += 1;
+            }
+        } else if name == "env" && arg.contains('=') {
+            // a variable set in the environment of the command
+        } else if leading_operands > 0 {
+            leading_operands -= 1;
+        } else {
+            return Some(index);
+        }
+
+        index += 1;
+    }
+
+    None
+}
+
+/// Split a shell command line into its simple commands.
+pub fn parse_command_line(line: &str) -> Vec<SimpleCommand> {
+    let mut commands = Vec::new();
+    let mut command = SimpleCommand::default();
+    let mut fd = None;
+
+    let generator = ShellTokenGenerator {};
+    let mut tokens = generator.tokenize(line).into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token.token.class {
+            ShellToken::Word => {
+                command.extend_span(&token.span);
+                command
+                    .expansions
+                    .extend(Expansion::from_word(line, &token));
+
+                if command.words.is_empty() && is_assignment(&token) {
+                    command.assignments.push(token.token.value);
+                } else {
+                    command.words.push(token.token.value);
+                }
+            }
+            ShellToken::IoNumber => {
+                command.extend_span(&token.span);
+                fd = token.token.value.parse().ok();
+            }
+            ShellToken::Newline | ShellToken::Operator => {
+                let operator = token.token.value.as_str();
+
+                let separator = match operator {
+                    "\n" => Some(Separator::Sequence),
+                    _ => Separator::from_operator(operator),
+                };
+
+                // '(' and ')' group commands into a subshell, which ends the current command
+                if separator.is_some() || operator == "(" || operator == ")" || operator == ";;" {
+                    if !command.is_empty() {
+                        command.separator = separator;
+                        commands.push(command);
+                        command = SimpleCommand::default();
+                    }
+                    continue;
+                }
+
+                // otherwise, it is a redirection
+                command.extend_span(&token.span);
+
+                let target = match tokens.peek() {
+                    Some(word) if word.token.class == ShellToken::Word => {
+                        command.extend_span(&word.span);
+                        word.token.value.clone()
+                    }
+                    _ => String::new(),
+                };
+
+                if !target.is_empty() {
+                    tokens.next();
+                }
+
+                command.redirections.push(Redirection {
+                    fd: fd.take(),
+                    operator: operator.to_owned(),
+                    target,
+                });
+            }
+            // the heredoc's redirection already explains where its lines go
+            ShellToken::HeredocBody => {}
+        }
+    }
+
+    if !command.is_empty() {
+        commands.push(command);
+    }
+
+    commands
+}
+
+/// Quote a word so the shell would read it back as the same single word,
+/// i.e. "it's here" becomes 'it'\''s here'
+pub fn quote(word: &str) -> String {
+    let is_plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=+./:,@%".contains(c));
+
+    if is_plain {
+        word.to_owned()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// True if the word is a variable assignment like 'NAME=value'.
+/// The name must not be quoted, so "'NAME=value'" is an ordinary word.
+fn is_assignment(word: &ShellWord) -> bool {
+    let first = match word.parts.first() {
+        Some(part) if part.kind == WordPartKind::Literal && !part.quoted => &part.value,
+        _ => return false,
+    };
+
+    match first.find('=') {
+        Some(index) if index > 0 => first[..index]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pipeline_with_redirections() {
+        let commands =
+            parse_command_line("find . -name \"*.rs\" | xargs grep -n foo > out.txt 2>&1");
+
+        assert_eq!(commands.len(), 2);
+
+        assert_eq!(commands[0].words, words(&["find", ".", "-name", "*.rs"]));
+        assert_eq!(commands[0].separator, Some(Separator::Pipe));
+
+        assert_eq!(commands[1].words, words(&["xargs", "grep", "-n", "foo"]));
+        assert_eq!(commands[1].separator, None);
+        assert_eq!(
+            commands[1].redirections,
+            vec![
+                Redirection {
+                    fd: None,
+                    operator: ">".to_owned(),
+                    target: "out.txt".to_owned(),
+                },
+                Redirection {
+                    fd: Some(2),
+                    operator: ">&".to_owned(),
+                    target: "1".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_chains() {
+        let chain = |line: &str| -> Vec<Vec<String>> {
+            let commands = parse_command_line(line);
+            commands[0]
+                .command_chain()
+                .iter()
+                .map(|words| words.to_vec())
+                .collect()
+        };
+
+        assert_eq!(
+            chain("xargs grep -n foo"),
+            vec![words(&["xargs"]), words(&["grep", "-n", "foo"])]
+        );
+        assert_eq!(
+            chain("xargs -n 1 -0 grep foo"),
+            vec![words(&["xargs", "-n", "1", "-0"]), words(&["grep", "foo"])]
+        );
+        assert_eq!(
+            chain("sudo -u root nice -n 5 make -j4"),
+            vec![
+                words(&["sudo", "-u", "root"]),
+                words(&["nice", "-n", "5"]),
+                words(&["make", "-j4"]),
+            ]
+        );
+        assert_eq!(
+            chain("env -i LANG=C sort -k2"),
+            vec![words(&["env", "-i", "LANG=C"]), words(&["sort", "-k2"])]
+        );
+        assert_eq!(
+            chain("timeout -s KILL 5s curl -s url"),
+            vec![
+                words(&["timeout", "-s", "KILL", "5s"]),
+                words(&["curl", "-s", "url"]),
+            ]
+        );
+        assert_eq!(chain("xargs -n 1"), vec![words(&["xargs", "-n", "1"])]);
+        assert_eq!(chain("grep -n foo"), vec![words(&["grep", "-n", "foo"])]);
+    }
+
+    #[test]
+    fn test_lists() {
+        let commands = parse_command_line("make && make install || echo 'it failed'; ls");
+
+        let separators: Vec<Option<Separator>> = commands.iter().map(|c| c.separator).collect();
+        assert_eq!(
+            separators,
+            vec![
+                Some(Separator::And),
+                Some(Separator::Or),
+                Some(Separator::Sequence),
+                None,
+            ]
+        );
+
+        assert_eq!(commands[2].words, words(&["echo", "it failed"]));
+    }
+
+    #[test]
+    fn test_assignments_and_escapes() {
+        let commands = parse_command_line("LANG=C sort a\\ b --key=2");
+
+        assert_eq!(commands[0].assignments, words(&["LANG=C"]));
+        assert_eq!(commands[0].words, words(&["sort", "a b", "--key=2"]));
+    }
+
+    #[test]
+    fn test_quote_round_trip() {
+        let original = words(&["grep", "it's here", "--color=auto", "*.rs"]);
+        let quoted: Vec<String> = original.iter().map(|w| quote(w)).collect();
+
+        let commands = parse_command_line(&quoted.join(" "));
+        assert_eq!(commands[0].words, original);
+    }
+
+    #[test]
+    fn test_spans_and_expansions() {
+        let line = "echo \"$HOME\" $(date) | grep -c *.rs";
+        let commands = parse_command_line(line);
+
+        assert_eq!(&line[commands[0].span.clone()], "echo \"$HOME\" $(date)");
+        assert_eq!(&line[commands[1].span.clone()], "grep -c *.rs");
+
+        let explained: Vec<(String, String)> = commands
+            .iter()
+            .flat_map(|c| c.expansions.iter())
+            .map(|e| (line[e.span.clone()].to_owned(), e.explain()))
+            .collect();
+
+        assert_eq!(
+            explained,
+            vec![
+                (
+                    "$HOME".to_owned(),
+                    "the value of the variable HOME".to_owned()
+                ),
+                (
+                    "$(date)".to_owned(),
+                    "the output of the command date".to_owned()
+                ),
+                (
+                    "*.rs".to_owned(),
+                    "the names of files matching *.rs".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_expansions() {
+        for (line, explained) in [
+            ("echo $(date", "the output of the command date"),
+            ("echo `date", "the output of the command date"),
+            ("echo $(", "the output of the command "),
+            ("echo ${HOME", "the value of the variable HOME"),
+            ("echo $((1+2", "the result of the arithmetic expression 1+2"),
+            ("echo $((éa", "the result of the arithmetic expression éa"),
+        ] {
+            let commands = parse_command_line(line);
+            let expansions = &commands[0].expansions;
+
+            assert_eq!(expansions.len(), 1, "{}", line);
+            assert_eq!(expansions[0].explain(), explained, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_newlines_and_heredocs() {
+        let commands = parse_command_line("cat <<EOF\nhello\nEOF\nls -l");
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].separator, Some(Separator::Sequence));
+        assert_eq!(commands[0].redirections[0].target, "EOF");
+        assert_eq!(commands[1].words, words(&["ls", "-l"]));
+    }
+
+    #[test]
+    fn test_explain_redirections() {
+        let commands = parse_command_line("cmd 2>&1 >> log < in");
+
+        let explained: Vec<String> = commands[0]
+            .redirections
+            .iter()
+            .map(|r| r.explain())
+            .collect();
+
+        assert_eq!(
+            explained,
+            vec![
+                "send standard error to wherever standard output goes",
+                "append standard output to the file log",
+                "read standard input from the file in",
+            ]
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod shell_token_generator;
 pub mod split_whitespace;
 pub mod token;
 pub mod token_generator;
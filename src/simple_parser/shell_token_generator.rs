@@ -0,0 +1,636 @@
+///
+/// tokenization of POSIX shell command lines.
+///
+/// Unlike troff, shell words can't be found by splitting on whitespace,
+/// since quoted whitespace is part of a word, so this generator reads the
+/// whole input itself rather than going through tokenizer::tokenize().
+///
+use simple_parser::token::{Token, TokenClass};
+use std::ops::Range;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ShellToken {
+    /// A word, such as a command name or an argument
+    Word,
+
+    /// A control operator like '|' or '&&', or a redirection operator like '>'
+    Operator,
+
+    /// A file descriptor written directly before a redirection, like the '2' in '2>&1'
+    IoNumber,
+
+    /// An unquoted newline, which ends a command the same as ';'
+    Newline,
+
+    /// The lines of a heredoc, which follow the line its marker ('<<EOF') is on
+    HeredocBody,
+}
+
+// Flag our ShellToken class with unit trait to prove it is a Token
+impl TokenClass for ShellToken {}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum WordPartKind {
+    /// Plain text, whether or not it was quoted
+    Literal,
+
+    /// $name, ${name}, ${name:-default}, $1, $@, etc.
+    Parameter,
+
+    /// $(command) or `command`
+    CommandSubstitution,
+
+    /// $((expression))
+    Arithmetic,
+
+    /// An unquoted pattern character: '*', '?' or a bracket expression like '[abc]'
+    Glob,
+}
+
+/// A piece of a word. I.e., the word "$HOME"/'*.rs' has two parts:
+/// the parameter $HOME and the literal /*.rs
+#[derive(Debug, PartialEq, Clone)]
+pub struct WordPart {
+    pub kind: WordPartKind,
+
+    /// For literals, the text with quotes and escapes removed.
+    /// For everything else, the text as written, i.e. "$HOME" or "[abc]".
+    pub value: String,
+
+    /// True if the part was within quotes or escaped.
+    pub quoted: bool,
+
+    /// The location of the part in the input, as byte offsets.
+    pub span: Range<usize>,
+}
+
+/// A token of a shell command line, with its location in the input.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShellWord {
+    /// Words have quotes and escapes removed from their value,
+    /// although expansions like $HOME are kept as written, since we never evaluate them.
+    pub token: Token<ShellToken>,
+
+    /// The location of the token in the input, as byte offsets.
+    pub span: Range<usize>,
+
+    /// The parts making up a word. Empty for every other kind of token.
+    pub parts: Vec<WordPart>,
+}
+
+/// Operators, longest first, so the longest possible match is taken.
+const OPERATORS: &[&str] = &[
+    "&>>", "<<<", "<<-", "&&", "||", ";;", ">>", ">&", "<&", ">|", "<>", "<<", "&>", "|", "&", ";",
+    "<", ">", "(", ")",
+];
+
+/// Chars that end an unquoted word, besides whitespace
+const OPERATOR_CHARS: &str = "|&;<>()";
+
+/// Special parameters that are a single char, like $? or $1
+const SPECIAL_PARAMETERS: &str = "@*#?$!-0123456789";
+
+pub struct ShellTokenGenerator;
+
+impl ShellTokenGenerator {
+    /// Split a shell command line into words and operators.
+    pub fn tokenize(&self, input: &str) -> Vec<ShellWord> {
+        let mut lexer = Lexer {
+            input,
+            pos: 0,
+            starts_line: true,
+            tokens: Vec::new(),
+            pending_heredocs: Vec::new(),
+        };
+
+        lexer.run();
+
+        lexer.tokens
+    }
+}
+
+struct Lexer<'s> {
+    input: &'s str,
+
+    /// byte offset of the next char to read
+    pos: usize,
+
+    /// true until a token has been read on the current line
+    starts_line: bool,
+
+    tokens: Vec<ShellWord>,
+
+    /// Heredocs whose marker we've read, but whose body we haven't yet.
+    /// Holds the delimiter, and whether leading tabs are stripped ('<<-')
+    pending_heredocs: Vec<(String, bool)>,
+}
+
+impl<'s> Lexer<'s> {
+    fn run(&mut self) {
+        // set after reading '<<', since the next word is the heredoc delimiter
+        let mut expect_heredoc: Option<bool> = None;
+
+        while let Some(c) = self.peek() {
+            let start = self.pos;
+
+            match c {
+                ' ' | '\t' => self.bump(),
+                '\\' if self.rest().starts_with("\\\n") => self.pos += 2,
+                '\n' => {
+                    self.bump();
+                    self.push(ShellToken::Newline, "\n".into(), start, Vec::new());
+                    self.starts_line = true;
+                    self.read_heredoc_bodies();
+                }
+                '#' => {
+                    // a comment, up to the end of the line
+                    let len = self.rest().find('\n').unwrap_or_else(|| self.rest().len());
+                    self.pos += len;
+                }
+                _ => {
+                    if let Some(operator) = OPERATORS.iter().find(|op| self.rest().starts_with(*op))
+                    {
+                        self.pos += operator.len();
+                        self.push(
+                            ShellToken::Operator,
+                            operator.to_string(),
+                            start,
+                            Vec::new(),
+                        );
+
+                        if *operator == "<<" || *operator == "<<-" {
+                            expect_heredoc = Some(*operator == "<<-");
+                        }
+                    } else if let Some(len) = self.io_number_len() {
+                        self.pos += len;
+                        let number = self.input[start..self.pos].to_owned();
+                        self.push(ShellToken::IoNumber, number, start, Vec::new());
+                    } else {
+                        let parts = self.read_word();
+                        let value: String = parts.iter().map(|p| p.value.as_str()).collect();
+
+                        if let Some(strip_tabs) = expect_heredoc.take() {
+                            self.pending_heredocs.push((value.clone(), strip_tabs));
+                        }
+
+                        self.push(ShellToken::Word, value, start, parts);
+                    }
+                }
+            }
+        }
+    }
+
+    /// If the input is at a file descriptor number directly followed by a redirection,
+    /// like '2>', return the length of the number.
+    fn io_number_len(&self) -> Option<usize> {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+
+        if len > 0 && (rest[len..].starts_with('<') || rest[len..].starts_with('>')) {
+            Some(len)
+        } else {
+            None
+        }
+    }
+
+    /// Read a single word, up to unquoted whitespace or an operator.
+    fn read_word(&mut self) -> Vec<WordPart> {
+        let mut parts = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || OPERATOR_CHARS.contains(c) {
+                break;
+            }
+
+            let start = self.pos;
+
+            match c {
+                '\'' => {
+                    self.bump();
+                    let len = self.rest().find('\'').unwrap_or_else(|| self.rest().len());
+                    let quoted = self.input[self.pos..self.pos + len].to_owned();
+                    self.pos += len;
+                    self.bump();
+
+                    push_literal(&mut parts, &quoted, true, start..self.pos);
+                }
+                '"' => {
+                    self.bump();
+                    self.read_double_quoted(&mut parts);
+                }
+                '\\' => {
+                    self.bump();
+                    if self.peek() == Some('\n') {
+                        // line continuation
+                        self.bump();
+                    } else if let Some(escaped) = self.peek() {
+                        self.bump();
+                        push_literal(&mut parts, &escaped.to_string(), true, start..self.pos);
+                    }
+                }
+                '$' | '`' => parts.push(self.read_expansion(false)),
+                '*' | '?' => {
+                    self.bump();
+                    parts.push(WordPart {
+                        kind: WordPartKind::Glob,
+                        value: c.to_string(),
+                        quoted: false,
+                        span: start..self.pos,
+                    });
+                }
+                '[' if self.bracket_expression_len().is_some() => {
+                    self.pos += self.bracket_expression_len().unwrap();
+                    parts.push(WordPart {
+                        kind: WordPartKind::Glob,
+                        value: self.input[start..self.pos].to_owned(),
+                        quoted: false,
+                        span: start..self.pos,
+                    });
+                }
+                _ => {
+                    self.bump();
+                    push_literal(&mut parts, &c.to_string(), false, start..self.pos);
+                }
+            }
+        }
+
+        parts
+    }
+
+    /// Read the inside of a double quoted string, after the opening quote.
+    /// Only '$', '`' and '\' keep their special meaning here.
+    fn read_double_quoted(&mut self, parts: &mut Vec<WordPart>) {
+        while let Some(c) = self.peek() {
+            let start = self.pos;
+
+            match c {
+                '"' => {
+                    self.bump();
+                    return;
+                }
+                '\\' => {
+                    self.bump();
+                    match self.peek() {
+                        Some('\n') => self.bump(),
+                        Some(escaped) if "$`\"\\".contains(escaped) => {
+                            self.bump();
+                            push_literal(parts, &escaped.to_string(), true, start..self.pos);
+                        }
+                        _ => push_literal(parts, "\\", true, start..self.pos),
+                    }
+                }
+                '$' | '`' => parts.push(self.read_expansion(true)),
+                _ => {
+                    self.bump();
+                    push_literal(parts, &c.to_string(), true, start..self.pos);
+                }
+            }
+        }
+    }
+
+    /// Read an expansion starting at '$' or '`'.
+    /// A '$' that doesn't begin an expansion is just a literal '$'.
+    fn read_expansion(&mut self, quoted: bool) -> WordPart {
+        let start = self.pos;
+        let rest = self.rest();
+
+        let (kind, len) = if rest.starts_with('`') {
+            (WordPartKind::CommandSubstitution, self.backticks_len())
+        } else if rest.starts_with("$((") {
+            (WordPartKind::Arithmetic, self.balanced_len(1, '(', ')'))
+        } else if rest.starts_with("$(") {
+            (
+                WordPartKind::CommandSubstitution,
+                self.balanced_len(1, '(', ')'),
+            )
+        } else if rest.starts_with("${") {
+            (WordPartKind::Parameter, self.balanced_len(1, '{', '}'))
+        } else {
+            let name_len = rest[1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len() - 1);
+
+            match rest[1..].chars().next() {
+                Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                    (WordPartKind::Parameter, 1 + name_len)
+                }
+                Some(c) if SPECIAL_PARAMETERS.contains(c) => (WordPartKind::Parameter, 2),
+                _ => (WordPartKind::Literal, 1),
+            }
+        };
+
+        self.pos += len;
+
+        WordPart {
+            kind,
+            value: self.input[start..self.pos].to_owned(),
+            quoted,
+            span: start..self.pos,
+        }
+    }
+
+    /// The length of a backtick command substitution, including both backticks.
+    fn backticks_len(&self) -> usize {
+        let rest = self.rest();
+        let mut escaped = false;
+
+        for (index, c) in rest.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '`' => return index + 1,
+                _ => {}
+            }
+        }
+
+        rest.len()
+    }
+
+    /// The length of a balanced group like '(a (b) c)', starting at the given offset from
+    /// the current position, which must be the opening char.
+    /// Quoted text within the group is skipped over.
+    fn balanced_len(&self, offset: usize, open: char, close: char) -> usize {
+        let rest = &self.rest()[offset..];
+        let mut depth = 0;
+        let mut chars = rest.char_indices();
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '\'' => {
+                    for (_, quoted) in chars.by_ref() {
+                        if quoted == '\'' {
+                            break;
+                        }
+                    }
+                }
+                '"' => {
+                    while let Some((_, quoted)) = chars.next() {
+                        match quoted {
+                            '\\' => {
+                                chars.next();
+                            }
+                            '"' => break,
+                            _ => {}
+                        }
+                    }
+                }
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return offset + index + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // unbalanced, so the group runs to the end of the input
+        offset + rest.len()
+    }
+
+    /// If the input is at a bracket expression like '[abc]' or '[!0-9]',
+    /// return its length.
+    fn bracket_expression_len(&self) -> Option<usize> {
+        let mut chars = self.rest().char_indices().skip(1).peekable();
+
+        // a ']' directly after the '[' (or '[!') is part of the expression, not its end
+        chars.next_if(|&(_, c)| c == '!' || c == '^');
+        chars.next()?;
+
+        for (index, c) in chars {
+            if c == ']' {
+                return Some(index + 1);
+            }
+
+            if c.is_whitespace() {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    /// Read the body of each heredoc whose marker was on the line just ended.
+    fn read_heredoc_bodies(&mut self) {
+        let pending: Vec<(String, bool)> = self.pending_heredocs.drain(..).collect();
+
+        for (delimiter, strip_tabs) in pending {
+            let body_start = self.pos;
+            let mut body_end = self.input.len();
+
+            while self.pos < self.input.len() {
+                let line_start = self.pos;
+                let line_len = self.rest().find('\n').unwrap_or_else(|| self.rest().len());
+                let line = &self.input[line_start..line_start + line_len];
+
+                self.pos = (line_start + line_len + 1).min(self.input.len());
+
+                let line = if strip_tabs {
+                    line.trim_start_matches('\t')
+                } else {
+                    line
+                };
+
+                if line == delimiter {
+                    body_end = line_start;
+                    break;
+                }
+            }
+
+            let body = self.input[body_start..body_end].to_owned();
+            self.tokens.push(ShellWord {
+                token: Token::new(ShellToken::HeredocBody, body, true),
+                span: body_start..body_end,
+                parts: Vec::new(),
+            });
+        }
+    }
+
+    fn push(&mut self, class: ShellToken, value: String, start: usize, parts: Vec<WordPart>) {
+        self.tokens.push(ShellWord {
+            token: Token::new(class, value, self.starts_line),
+            span: start..self.pos,
+            parts,
+        });
+
+        self.starts_line = false;
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+}
+
+/// Add literal text to the parts of a word,
+/// joining it with the previous part if that is a literal of the same quoting.
+fn push_literal(parts: &mut Vec<WordPart>, text: &str, quoted: bool, span: Range<usize>) {
+    if let Some(last) = parts.last_mut() {
+        if last.kind == WordPartKind::Literal
+            && last.quoted == quoted
+            && last.span.end == span.start
+        {
+            last.value.push_str(text);
+            last.span.end = span.end;
+            return;
+        }
+    }
+
+    parts.push(WordPart {
+        kind: WordPartKind::Literal,
+        value: text.to_owned(),
+        quoted,
+        span,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summarize(input: &str) -> Vec<(ShellToken, String, &str)> {
+        let generator = ShellTokenGenerator {};
+
+        generator
+            .tokenize(input)
+            .into_iter()
+            .map(|w| (w.token.class, w.token.value, &input[w.span]))
+            .collect()
+    }
+
+    #[test]
+    fn test_quoted_whitespace() {
+        let actual = summarize("grep 'hello world' \"a  b\" c\\ d");
+
+        let expected = vec![
+            (ShellToken::Word, "grep".to_owned(), "grep"),
+            (ShellToken::Word, "hello world".to_owned(), "'hello world'"),
+            (ShellToken::Word, "a  b".to_owned(), "\"a  b\""),
+            (ShellToken::Word, "c d".to_owned(), "c\\ d"),
+        ];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_operators_and_io_numbers() {
+        let actual = summarize("a|b&&c 2>&1 >>log");
+
+        let expected = vec![
+            (ShellToken::Word, "a".to_owned(), "a"),
+            (ShellToken::Operator, "|".to_owned(), "|"),
+            (ShellToken::Word, "b".to_owned(), "b"),
+            (ShellToken::Operator, "&&".to_owned(), "&&"),
+            (ShellToken::Word, "c".to_owned(), "c"),
+            (ShellToken::IoNumber, "2".to_owned(), "2"),
+            (ShellToken::Operator, ">&".to_owned(), ">&"),
+            (ShellToken::Word, "1".to_owned(), "1"),
+            (ShellToken::Operator, ">>".to_owned(), ">>"),
+            (ShellToken::Word, "log".to_owned(), "log"),
+        ];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_expansions() {
+        let generator = ShellTokenGenerator {};
+        let words =
+            generator.tokenize("echo \"$HOME/x\" ${A:-b} $(ls \"a)\" | wc) `pwd` $((1+(2)))");
+
+        let parts: Vec<(WordPartKind, String, bool)> = words
+            .iter()
+            .flat_map(|w| w.parts.iter())
+            .map(|p| (p.kind, p.value.clone(), p.quoted))
+            .collect();
+
+        let expected = vec![
+            (WordPartKind::Literal, "echo".to_owned(), false),
+            (WordPartKind::Parameter, "$HOME".to_owned(), true),
+            (WordPartKind::Literal, "/x".to_owned(), true),
+            (WordPartKind::Parameter, "${A:-b}".to_owned(), false),
+            (
+                WordPartKind::CommandSubstitution,
+                "$(ls \"a)\" | wc)".to_owned(),
+                false,
+            ),
+            (WordPartKind::CommandSubstitution, "`pwd`".to_owned(), false),
+            (WordPartKind::Arithmetic, "$((1+(2)))".to_owned(), false),
+        ];
+
+        assert_eq!(parts, expected);
+    }
+
+    #[test]
+    fn test_globs() {
+        let generator = ShellTokenGenerator {};
+        let words = generator.tokenize("ls *.rs '*.md' file[0-9]?");
+
+        let kinds: Vec<Vec<WordPartKind>> = words
+            .iter()
+            .map(|w| w.parts.iter().map(|p| p.kind).collect())
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                vec![WordPartKind::Literal],
+                vec![WordPartKind::Glob, WordPartKind::Literal],
+                vec![WordPartKind::Literal],
+                vec![
+                    WordPartKind::Literal,
+                    WordPartKind::Glob,
+                    WordPartKind::Glob,
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_brackets() {
+        let generator = ShellTokenGenerator {};
+
+        for (input, kinds) in [
+            ("[é]", vec![WordPartKind::Glob]),
+            ("[!é]x", vec![WordPartKind::Glob, WordPartKind::Literal]),
+            ("[é", vec![WordPartKind::Literal]),
+        ] {
+            let words = generator.tokenize(input);
+            let actual: Vec<WordPartKind> = words[0].parts.iter().map(|p| p.kind).collect();
+
+            assert_eq!(actual, kinds, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_heredoc() {
+        let actual = summarize("cat <<-'EOF' | wc\n\thello\n\tEOF\nls # comment\n");
+
+        let expected = vec![
+            (ShellToken::Word, "cat".to_owned(), "cat"),
+            (ShellToken::Operator, "<<-".to_owned(), "<<-"),
+            (ShellToken::Word, "EOF".to_owned(), "'EOF'"),
+            (ShellToken::Operator, "|".to_owned(), "|"),
+            (ShellToken::Word, "wc".to_owned(), "wc"),
+            (ShellToken::Newline, "\n".to_owned(), "\n"),
+            (ShellToken::HeredocBody, "\thello\n".to_owned(), "\thello\n"),
+            (ShellToken::Word, "ls".to_owned(), "ls"),
+            (ShellToken::Newline, "\n".to_owned(), "\n"),
+        ];
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -32,6 +32,9 @@ impl<'a> Iterator for SplitWhitespaceInclusive<'a> {
             cur_idx = cur_idx + c.len_utf8();
 
             if cur_idx == c.len_utf8() {
+                // the first char may be more than one byte
+                result_end = result_start + cur_idx;
+
                 if c.is_whitespace() {
                     whitespace_mode = true;
                     whitespace_char = c;
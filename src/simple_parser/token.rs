@@ -2,7 +2,7 @@
 // Implement this trait on an enum of your token types.
 pub trait TokenClass {}
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct Token<C: TokenClass> {
     pub class: C,
     pub value: String,
@@ -1,30 +1,373 @@
+extern crate unicode_width;
+
+use self::unicode_width::UnicodeWidthStr;
+use std::env;
+
+/// The escape sequences of terminal styling begin with this,
+/// and end with 'm', i.e. "\x1b[1m" for bold.
+const SGR_START: &str = "\x1b[";
+const SGR_END: &str = "m";
+
+/// A color, as a terminal shows it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Color {
+    /// One of the 16 standard colors: 0 to 7, then their bright versions 8 to 15
+    Standard(u8),
+
+    /// One of the 256 colors of the extended palette
+    Indexed(u8),
+
+    /// A 24-bit color
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// The SGR parameters that set this color, as the foreground or background.
+    fn sgr_codes(self, background: bool) -> String {
+        let offset = if background { 10 } else { 0 };
+
+        match self {
+            Color::Standard(n) if n < 8 => (30 + offset + n).to_string(),
+            Color::Standard(n) => (90 + offset + (n - 8).min(7)).to_string(),
+            Color::Indexed(n) => format!("{};5;{}", 38 + offset, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", 38 + offset, r, g, b),
+        }
+    }
+}
+
+/// The 16 standard colors, as xterm shows them.
+const STANDARD_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The levels of each component in the 6x6x6 color cube of the extended palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How many colors a terminal can show.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorDepth {
+    /// The 16 standard colors
+    Standard,
+
+    /// The 256 colors of the extended palette
+    Indexed,
+
+    /// Any 24-bit color
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// The color depth of the terminal, going by COLORTERM, then TERM.
+    pub fn detect() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Indexed
+        } else {
+            ColorDepth::Standard
+        }
+    }
+
+    /// The closest color to the given 24-bit color that this depth can show.
+    pub fn closest(self, r: u8, g: u8, b: u8) -> Color {
+        match self {
+            ColorDepth::TrueColor => Color::Rgb(r, g, b),
+            ColorDepth::Indexed => closest_indexed(r, g, b),
+            ColorDepth::Standard => {
+                let closest = (0..STANDARD_COLORS.len())
+                    .min_by_key(|&i| distance((r, g, b), STANDARD_COLORS[i]))
+                    .unwrap_or(0);
+
+                Color::Standard(closest as u8)
+            }
+        }
+    }
+}
+
+/// The closest color of the extended palette, either from its color cube or its grays.
+fn closest_indexed(r: u8, g: u8, b: u8) -> Color {
+    let level = |c: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+
+    // the 24 grays run from 8 to 238, in steps of 10
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_index;
+    let gray = (gray_level, gray_level, gray_level);
+
+    if distance((r, g, b), gray) < distance((r, g, b), cube) {
+        Color::Indexed(232 + gray_index)
+    } else {
+        Color::Indexed(16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8)
+    }
+}
+
+/// The squared distance between two colors.
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+    let dr = a.0 as i32 - b.0 as i32;
+    let dg = a.1 as i32 - b.1 as i32;
+    let db = a.2 as i32 - b.2 as i32;
+
+    dr * dr + dg * dg + db * db
+}
+
+/// How italic text is shown.
+/// Many terminals can't show italics, so as in man, underlining stands in for them by default.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ItalicStyle {
+    #[default]
+    Underline,
+    Italic,
+}
+
+/// The attributes text is written with.
+/// Rather than wrapping each piece of text in its own escapes,
+/// output moves from one style to the next with transition_to().
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+impl TextStyle {
+    /// True if this is the terminal's default style, without any attributes.
+    pub fn is_plain(&self) -> bool {
+        *self == TextStyle::default()
+    }
+
+    /// The escape sequence that changes text written in this style to the given style,
+    /// setting only the attributes that differ. Empty if the styles are the same.
+    pub fn transition_to(&self, next: &TextStyle) -> String {
+        if self == next {
+            return String::new();
+        }
+
+        if next.is_plain() {
+            return sgr(&["0".to_owned()]);
+        }
+
+        let mut codes = Vec::new();
+
+        // bold and dim are both turned off by the same code
+        if (self.bold && !next.bold) || (self.dim && !next.dim) {
+            codes.push("22".to_owned());
+
+            if next.bold {
+                codes.push("1".to_owned());
+            }
+            if next.dim {
+                codes.push("2".to_owned());
+            }
+        } else {
+            if next.bold && !self.bold {
+                codes.push("1".to_owned());
+            }
+            if next.dim && !self.dim {
+                codes.push("2".to_owned());
+            }
+        }
+
+        if self.italic != next.italic {
+            codes.push(if next.italic { "3" } else { "23" }.to_owned());
+        }
+
+        if self.underline != next.underline {
+            codes.push(if next.underline { "4" } else { "24" }.to_owned());
+        }
+
+        if self.foreground != next.foreground {
+            codes.push(match next.foreground {
+                Some(color) => color.sgr_codes(false),
+                None => "39".to_owned(),
+            });
+        }
+
+        if self.background != next.background {
+            codes.push(match next.background {
+                Some(color) => color.sgr_codes(true),
+                None => "49".to_owned(),
+            });
+        }
+
+        sgr(&codes)
+    }
+}
+
+/// How many columns of a terminal the text takes up: combining marks take none,
+/// wide characters like CJK and emoji take two, and escape sequences aren't shown at all.
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut rest = text;
+
+    while let Some(start) = rest.find('\x1b') {
+        width += rest[..start].width();
+        rest = &rest[start + 1..];
+
+        // a control sequence, like an SGR escape, ends with a byte from '@' to '~'
+        if let Some(sequence) = rest.strip_prefix('[') {
+            let end = sequence
+                .find(|c| ('@'..='~').contains(&c))
+                .map_or(sequence.len(), |i| i + 1);
+            rest = &sequence[end..];
+        }
+    }
+
+    width + rest.width()
+}
+
+/// The escape sequence setting the given SGR parameters.
+fn sgr(codes: &[String]) -> String {
+    format!("{}{}{}", SGR_START, codes.join(";"), SGR_END)
+}
+
 pub trait TextFormat {
+    fn styled(self, style: &TextStyle) -> String;
     fn bold(self) -> String;
-    fn italic(self) -> String;
-    fn underlined(self) -> String;
 }
 
 impl<'a> TextFormat for &'a str {
+    /// The text in the given style, followed by a return to the default style.
+    fn styled(self, style: &TextStyle) -> String {
+        let plain = TextStyle::default();
+
+        format!(
+            "{}{}{}",
+            plain.transition_to(style),
+            self,
+            style.transition_to(&plain)
+        )
+    }
+
     fn bold(self) -> String {
-        const BOLD_TAG: &str = "\x1b[1m";
-        const BOLD_TAG_CLOSE: &str = "\x1b[0m";
+        self.styled(&TextStyle {
+            bold: true,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        format!("{}{}{}", BOLD_TAG, self, BOLD_TAG_CLOSE)
+    #[test]
+    fn test_transitions_change_only_what_differs() {
+        let plain = TextStyle::default();
+        let bold = TextStyle {
+            bold: true,
+            ..Default::default()
+        };
+        let bold_italic = TextStyle {
+            italic: true,
+            ..bold
+        };
+
+        assert_eq!(plain.transition_to(&plain), "");
+        assert_eq!(plain.transition_to(&bold), "\x1b[1m");
+        assert_eq!(bold.transition_to(&bold_italic), "\x1b[3m");
+        assert_eq!(bold_italic.transition_to(&bold), "\x1b[23m");
+        assert_eq!(bold_italic.transition_to(&plain), "\x1b[0m");
     }
 
-    fn italic(self) -> String {
-        // const italic_tag: &str = "\x1b[3m";
-        // const italic_tag_close: &str = "\x1b[0m";
+    #[test]
+    fn test_turning_off_bold_keeps_dim() {
+        let bold_dim = TextStyle {
+            bold: true,
+            dim: true,
+            ..Default::default()
+        };
+        let dim = TextStyle {
+            dim: true,
+            ..Default::default()
+        };
+
+        assert_eq!(bold_dim.transition_to(&dim), "\x1b[22;2m");
+    }
 
-        // format!("{}{}{}", italic_tag, self, italic_tag_close)
+    #[test]
+    fn test_colors() {
+        let plain = TextStyle::default();
+        let colored = TextStyle {
+            foreground: Some(Color::Standard(9)),
+            background: Some(Color::Rgb(1, 2, 3)),
+            ..Default::default()
+        };
+        let indexed = TextStyle {
+            foreground: Some(Color::Indexed(200)),
+            ..Default::default()
+        };
+
+        assert_eq!(plain.transition_to(&colored), "\x1b[91;48;2;1;2;3m");
+        assert_eq!(colored.transition_to(&indexed), "\x1b[38;5;200;49m");
+    }
+
+    #[test]
+    fn test_closest_colors() {
+        assert_eq!(ColorDepth::TrueColor.closest(1, 2, 3), Color::Rgb(1, 2, 3));
+
+        assert_eq!(ColorDepth::Indexed.closest(255, 0, 0), Color::Indexed(196));
+        assert_eq!(ColorDepth::Indexed.closest(0, 0, 135), Color::Indexed(18));
+        assert_eq!(
+            ColorDepth::Indexed.closest(128, 128, 128),
+            Color::Indexed(244)
+        );
+
+        assert_eq!(
+            ColorDepth::Standard.closest(200, 10, 10),
+            Color::Standard(1)
+        );
+        assert_eq!(
+            ColorDepth::Standard.closest(250, 250, 250),
+            Color::Standard(15)
+        );
+    }
 
-        self.underlined()
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("Nikšić"), 6);
+        assert_eq!(display_width("Nik\u{73}\u{30c}ic\u{301}"), 6);
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("🦀!"), 3);
+        assert_eq!(display_width("\x1b[1;34mbold\x1b[0m"), 4);
     }
 
-    fn underlined(self) -> String {
-        const UNDERLINE_TAG: &str = "\x1b[4m";
-        const UNDERLINE_TAG_CLOSE: &str = "\x1b[0m";
+    #[test]
+    fn test_text_format() {
+        assert_eq!("a".bold(), "\x1b[1ma\x1b[0m");
 
-        format!("{}{}{}", UNDERLINE_TAG, self, UNDERLINE_TAG_CLOSE)
+        let italic = TextStyle {
+            italic: true,
+            ..Default::default()
+        };
+        assert_eq!("a".styled(&italic), "\x1b[3ma\x1b[0m");
     }
 }